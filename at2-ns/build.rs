@@ -7,7 +7,7 @@ fn main() -> io::Result<()> {
     let mut conf = tonic_build::configure();
     conf = conf.build_client(false).build_server(false);
 
-    #[cfg(feature = "client")]
+    #[cfg(any(feature = "client", feature = "native-client"))]
     {
         conf = conf.build_client(true);
     }
@@ -0,0 +1,77 @@
+use std::time::SystemTime;
+
+use drop::crypto::sign;
+
+use crate::{from_timestamp, to_timestamp};
+
+/// Statement of an admin about a public key being an official one
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Attestation {
+    /// Public key attested
+    pub subject: sign::PublicKey,
+    /// If it is official, `false` to revoke a previous attestation
+    pub verified: bool,
+    /// Only the latest attestation for a subject is taken into account
+    pub issued_at: SystemTime,
+    /// Public key of the admin
+    pub admin: sign::PublicKey,
+    /// Signature of the rest by the admin
+    pub signature: sign::Signature,
+}
+
+impl Attestation {
+    /// Create a new attestation, signed by the given admin
+    pub fn new(
+        admin: &sign::KeyPair,
+        subject: sign::PublicKey,
+        verified: bool,
+        issued_at: SystemTime,
+    ) -> Result<Self, sign::SignError> {
+        let signature = admin.sign(&(subject, verified, to_timestamp(issued_at)))?;
+
+        Ok(Self {
+            subject,
+            verified,
+            issued_at,
+            admin: admin.public(),
+            signature,
+        })
+    }
+
+    /// Check that it was signed by its admin, not if the admin is trusted
+    pub fn verify(&self) -> Result<(), sign::VerifyError> {
+        self.signature.verify(
+            &(self.subject, self.verified, to_timestamp(self.issued_at)),
+            &self.admin,
+        )
+    }
+}
+
+impl TryFrom<crate::proto::Attestation> for Attestation {
+    type Error = bincode::Error;
+
+    fn try_from(attestation: crate::proto::Attestation) -> Result<Self, Self::Error> {
+        Ok(Self {
+            subject: bincode::deserialize(&attestation.subject)?,
+            verified: attestation.verified,
+            issued_at: from_timestamp(attestation.issued_at),
+            admin: bincode::deserialize(&attestation.admin)?,
+            signature: bincode::deserialize(&attestation.signature)?,
+        })
+    }
+}
+
+impl TryFrom<Attestation> for crate::proto::Attestation {
+    type Error = bincode::Error;
+
+    fn try_from(attestation: Attestation) -> Result<Self, Self::Error> {
+        Ok(Self {
+            subject: bincode::serialize(&attestation.subject)?,
+            verified: attestation.verified,
+            issued_at: to_timestamp(attestation.issued_at),
+            admin: bincode::serialize(&attestation.admin)?,
+            signature: bincode::serialize(&attestation.signature)?,
+        })
+    }
+}
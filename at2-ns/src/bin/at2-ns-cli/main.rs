@@ -0,0 +1,328 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fs, io,
+    path::{Path, PathBuf},
+    process,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use at2_ns::{
+    client::{self, NativeClient},
+    Attestation, Contact, Profile, User,
+};
+use drop::crypto::sign;
+use http::Uri;
+use snafu::{ensure, OptionExt, ResultExt};
+use structopt::StructOpt;
+
+/// Inspect and manage an AT2 name service
+#[derive(structopt::StructOpt)]
+struct Arguments {
+    /// URI of the name service
+    #[structopt(long)]
+    server: Uri,
+    #[structopt(subcommand)]
+    command: Command,
+}
+
+#[derive(structopt::StructOpt)]
+enum Command {
+    /// List the registered accounts
+    List,
+    /// Find an account by name or by public key
+    Lookup { query: String },
+    /// List the names used by an account, found by name or by public key
+    History { query: String },
+    /// Create a new keyfile, without registering it
+    Generate { name: String, keyfile: PathBuf },
+    /// Register the account stored in a keyfile
+    Register { keyfile: PathBuf },
+    /// Change the name of the account stored in a keyfile
+    Rename { keyfile: PathBuf, name: String },
+    /// Change the profile of the account stored in a keyfile, unset fields are cleared
+    Profile {
+        keyfile: PathBuf,
+        /// Hash of the avatar image, or a small one as a data URL
+        #[structopt(long)]
+        avatar: Option<String>,
+        #[structopt(long)]
+        bio: Option<String>,
+        #[structopt(long)]
+        url: Option<String>,
+    },
+    /// Mark an account, found by name or by public key, as official
+    Attest {
+        /// Keyfile of an admin of the name service
+        admin: PathBuf,
+        query: String,
+        /// Unmark it instead
+        #[structopt(long)]
+        revoke: bool,
+    },
+    /// Write the directory as a JSON list of contacts, as read by the speedtest
+    /// and by `register-all`
+    Export {
+        /// Where to write it, defaults to stdout
+        #[structopt(long)]
+        output: Option<PathBuf>,
+    },
+    /// Register every exported account, with its name and profile, given its keyfile.
+    ///
+    /// Attestations aren't registered again, as that needs an admin.
+    RegisterAll {
+        /// As written by `export`
+        contacts: PathBuf,
+        /// Of the accounts to register, others are reported as failed
+        keyfiles: Vec<PathBuf>,
+    },
+}
+
+#[derive(Debug, snafu::Snafu)]
+enum Error {
+    #[snafu(display("read {}: {}", path.display(), source))]
+    Read { path: PathBuf, source: io::Error },
+    #[snafu(display("write {}: {}", path.display(), source))]
+    Write { path: PathBuf, source: io::Error },
+    #[snafu(display("parse {}: {}", path.display(), source))]
+    Parse {
+        path: PathBuf,
+        source: serde_json::Error,
+    },
+    #[snafu(display("serialize: {}", source))]
+    Serialize { source: serde_json::Error },
+    #[snafu(display("no account found for {}", query))]
+    NotFound { query: String },
+    #[snafu(display("name service: {}", source))]
+    Client { source: client::Error },
+    #[snafu(display("sign: {}", source))]
+    Sign { source: sign::SignError },
+    #[snafu(display("{} accounts not registered", count))]
+    NotRegistered { count: usize },
+}
+
+fn read_json<T: serde::de::DeserializeOwned>(path: &Path) -> Result<T, Error> {
+    let content = fs::read(path).context(Read { path })?;
+
+    serde_json::from_slice(&content).context(Parse { path })
+}
+
+fn write_json<T: serde::Serialize>(path: &Path, value: &T) -> Result<(), Error> {
+    let content = serde_json::to_vec_pretty(value).context(Serialize)?;
+
+    fs::write(path, content).context(Write { path })
+}
+
+fn sorted(contacts: HashSet<Contact>) -> Vec<Contact> {
+    let mut contacts = contacts.into_iter().collect::<Vec<_>>();
+    contacts.sort_unstable_by(|a, b| a.name.cmp(&b.name));
+
+    contacts
+}
+
+/// Pair contacts with their keyfiles, also returning the ones without any
+fn with_keypairs(contacts: Vec<Contact>, keyfiles: Vec<User>) -> (Vec<User>, Vec<Contact>) {
+    let mut keypairs = keyfiles
+        .into_iter()
+        .map(|user| (user.public_key(), user.keypair().clone()))
+        .collect::<HashMap<_, _>>();
+
+    let mut users = Vec::new();
+    let mut missing = Vec::new();
+    for contact in contacts {
+        match keypairs.remove(contact.public_key()) {
+            Some(keypair) => {
+                let mut user = User::new(contact.name, keypair);
+                user.profile = contact.profile;
+                users.push(user);
+            }
+            None => missing.push(contact),
+        }
+    }
+
+    (users, missing)
+}
+
+fn print_contact(contact: &Contact) {
+    if contact.verified {
+        println!("{}\t{}\tverified", contact.name, contact.public_key());
+    } else {
+        println!("{}\t{}", contact.name, contact.public_key());
+    }
+}
+
+async fn lookup(client: &mut NativeClient, query: String) -> Result<Contact, Error> {
+    client
+        .get_all()
+        .await
+        .context(Client)?
+        .into_iter()
+        .find(|contact| contact.name == query || contact.public_key().to_string() == query)
+        .context(NotFound { query })
+}
+
+async fn run(arguments: Arguments) -> Result<(), Error> {
+    let mut client = NativeClient::new(&arguments.server);
+
+    match arguments.command {
+        Command::List => {
+            let contacts = client.get_all().await.context(Client)?;
+            sorted(contacts).iter().for_each(print_contact);
+        }
+        Command::Lookup { query } => print_contact(&lookup(&mut client, query).await?),
+        Command::History { query } => {
+            let contact = lookup(&mut client, query).await?;
+            let history = client
+                .get_history(contact.public_key())
+                .await
+                .context(Client)?;
+
+            for change in history.changes() {
+                let since = change.since.duration_since(UNIX_EPOCH).unwrap_or_default();
+                println!("{}\t{}", since.as_secs(), change.name);
+            }
+        }
+        Command::Generate { name, keyfile } => {
+            let user = User::new(name, sign::KeyPair::random());
+            write_json(&keyfile, &user)?;
+
+            print_contact(&user.to_thin());
+        }
+        Command::Register { keyfile } => {
+            let user: User = read_json(&keyfile)?;
+            client.put(user.clone()).await.context(Client)?;
+
+            print_contact(&user.to_thin());
+        }
+        Command::Rename { keyfile, name } => {
+            let mut user: User = read_json(&keyfile)?;
+            user.name = name;
+            client.put(user.clone()).await.context(Client)?;
+
+            // only store it once accepted
+            write_json(&keyfile, &user)?;
+
+            print_contact(&user.to_thin());
+        }
+        Command::Profile {
+            keyfile,
+            avatar,
+            bio,
+            url,
+        } => {
+            let mut user: User = read_json(&keyfile)?;
+            user.profile = Profile { avatar, bio, url };
+            client.put(user.clone()).await.context(Client)?;
+
+            write_json(&keyfile, &user)?;
+
+            print_contact(&user.to_thin());
+        }
+        Command::Attest {
+            admin,
+            query,
+            revoke,
+        } => {
+            let admin: User = read_json(&admin)?;
+            let contact = lookup(&mut client, query).await?;
+
+            let attestation = Attestation::new(
+                admin.keypair(),
+                *contact.public_key(),
+                !revoke,
+                SystemTime::now(),
+            )
+            .context(Sign)?;
+            client.attest(attestation).await.context(Client)?;
+
+            print_contact(&contact.with_verified(!revoke));
+        }
+        Command::Export { output } => {
+            let contacts = sorted(client.get_all().await.context(Client)?);
+
+            match output {
+                Some(path) => write_json(&path, &contacts)?,
+                None => println!(
+                    "{}",
+                    serde_json::to_string_pretty(&contacts).context(Serialize)?
+                ),
+            }
+        }
+        Command::RegisterAll { contacts, keyfiles } => {
+            let contacts: Vec<Contact> = read_json(&contacts)?;
+            let keyfiles = keyfiles
+                .iter()
+                .map(|keyfile| read_json(keyfile))
+                .collect::<Result<_, _>>()?;
+
+            let (users, missing) = with_keypairs(contacts, keyfiles);
+            let mut failed = missing.len();
+            for contact in missing {
+                eprintln!("unable to register {}: no keyfile", contact.name);
+            }
+
+            for user in users {
+                match client.put(user.clone()).await {
+                    Ok(()) => print_contact(&user.to_thin()),
+                    Err(err) => {
+                        eprintln!("unable to register {}: {}", user.name, err);
+                        failed += 1;
+                    }
+                }
+            }
+
+            ensure!(failed == 0, NotRegistered { count: failed });
+        }
+    }
+
+    Ok(())
+}
+
+#[tokio::main(flavor = "multi_thread")]
+async fn main() {
+    let arguments = Arguments::from_args();
+
+    run(arguments).await.unwrap_or_else(|err| {
+        eprintln!("error running cmd: {}", err);
+        process::exit(1);
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn user(name: &str, bio: &str) -> User {
+        let mut user = User::new(name.to_owned(), sign::KeyPair::random());
+        user.profile.bio = Some(bio.to_owned());
+
+        user
+    }
+
+    #[test]
+    fn exported_contacts_are_registered_back() {
+        let users = vec![user("first", "hello"), user("second", "world")];
+        let exported =
+            serde_json::to_vec_pretty(&sorted(users.iter().cloned().map(User::to_thin).collect()))
+                .expect("serialize");
+
+        let contacts = serde_json::from_slice(&exported).expect("deserialize");
+        let (registered, missing) = with_keypairs(contacts, users.clone());
+
+        assert_eq!(registered, users);
+        assert!(missing.is_empty());
+    }
+
+    #[test]
+    fn contacts_without_keyfile_are_missing() {
+        let known = user("known", "hello");
+        let unknown = user("unknown", "world");
+
+        let (registered, missing) = with_keypairs(
+            vec![known.clone().to_thin(), unknown.clone().to_thin()],
+            vec![known.clone()],
+        );
+
+        assert_eq!(registered, vec![known]);
+        assert_eq!(missing, vec![unknown.to_thin()]);
+    }
+}
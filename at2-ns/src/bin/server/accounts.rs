@@ -1,5 +1,9 @@
-use std::collections::{HashMap, HashSet};
+use std::{
+    collections::{HashMap, HashSet},
+    time::{Duration, SystemTime},
+};
 
+use at2_ns::{Contact, History, Profile};
 use drop::crypto::sign;
 use tokio::sync::{mpsc, oneshot};
 
@@ -7,6 +11,12 @@ use tokio::sync::{mpsc, oneshot};
 pub enum Error {
     #[snafu(display("name already registered"))]
     AlreadyExisting,
+    #[snafu(display("account not found"))]
+    NotFound,
+    #[snafu(display("a more recent claim was already accepted"))]
+    Outdated,
+    #[snafu(display("aborted as another put of the batch failed"))]
+    Aborted,
     #[snafu(display("gone on send"))]
     GoneOnSend,
     #[snafu(display("gone on recv"))]
@@ -15,37 +25,180 @@ pub enum Error {
 
 type Name = String;
 
+/// How far the time of a claim can be from the local one, as clocks are not synchronized
+pub const MAX_CLOCK_SKEW: Duration = Duration::from_secs(5 * 60);
+
+/// If a claim was signed around now, so that it can't be backdated nor replayed later
+pub fn is_recent(signed_at: SystemTime, now: SystemTime) -> bool {
+    signed_at + MAX_CLOCK_SKEW >= now && signed_at <= now + MAX_CLOCK_SKEW
+}
+
+/// Latest decision of an admin about an account being official
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct Verification {
+    pub verified: bool,
+    pub issued_at: SystemTime,
+}
+
+fn latest(a: Option<Verification>, b: Option<Verification>) -> Option<Verification> {
+    a.into_iter()
+        .chain(b)
+        .max_by_key(|verification| verification.issued_at)
+}
+
+/// What is known about a registered public key
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Account {
+    pub name: Name,
+    pub profile: Profile,
+    /// When the public key was first registered
+    pub registered_at: SystemTime,
+    /// When the name or the profile was last changed
+    pub updated_at: SystemTime,
+    /// When the owner last confirmed it, by putting it again
+    pub renewed_at: SystemTime,
+    /// `None` if never attested
+    pub verification: Option<Verification>,
+}
+
+impl Account {
+    /// If currently attested as official
+    pub fn is_verified(&self) -> bool {
+        self.verification
+            .map_or(false, |verification| verification.verified)
+    }
+}
+
+/// Everything known by the accounts, to restore them later
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct State {
+    pub accounts: HashMap<sign::PublicKey, Account>,
+    /// Names used by each public key, even if since dropped
+    pub history: HashMap<sign::PublicKey, History>,
+}
+
+/// Without any history, only the current names being known
+impl FromIterator<(sign::PublicKey, Account)> for State {
+    fn from_iter<T: IntoIterator<Item = (sign::PublicKey, Account)>>(iter: T) -> Self {
+        Self {
+            accounts: iter.into_iter().collect(),
+            history: HashMap::new(),
+        }
+    }
+}
+
 type Response<T> = oneshot::Sender<T>;
 
 enum Commands {
     Put {
         pubkey: Box<sign::PublicKey>,
         name: Name,
-        resp: Response<Result<(), Error>>,
+        profile: Profile,
+        signed_at: SystemTime,
+        resp: Response<Result<Account, Error>>,
     },
     GetAll {
-        resp: Response<HashMap<sign::PublicKey, Name>>,
+        resp: Response<HashSet<Contact>>,
+    },
+    Snapshot {
+        resp: Response<State>,
+    },
+    Merge {
+        accounts: Vec<(sign::PublicKey, Account, History)>,
+        resp: Response<()>,
+    },
+    History {
+        pubkey: Box<sign::PublicKey>,
+        resp: Response<History>,
+    },
+    Sweep {
+        resp: Response<Vec<sign::PublicKey>>,
+    },
+    Attest {
+        pubkey: Box<sign::PublicKey>,
+        verification: Verification,
+        resp: Response<Result<(), Error>>,
+    },
+    PutBatch {
+        puts: Vec<(sign::PublicKey, Name, Profile, SystemTime)>,
+        atomic: bool,
+        resp: Response<Vec<Result<Account, Error>>>,
+    },
+    Close {
+        resp: Response<State>,
     },
 }
 
+#[derive(Clone)]
 pub struct Accounts {
     agent: mpsc::Sender<Commands>,
 }
 
 impl Accounts {
     pub fn new() -> Self {
+        Self::with_ttl(None)
+    }
+
+    /// Accounts expire when not renewed during the given duration, if any
+    pub fn with_ttl(ttl: Option<Duration>) -> Self {
         Self {
-            agent: AccountsHandler::new().spawn(),
+            agent: AccountsHandler::new(ttl).spawn(),
+        }
+    }
+
+    /// Start from already registered accounts, failing if a name is used twice
+    pub fn restore(state: State, ttl: Option<Duration>) -> Result<Self, Error> {
+        let State {
+            accounts,
+            mut history,
+        } = state;
+
+        let mut names = HashSet::with_capacity(accounts.len());
+        if !accounts
+            .values()
+            .all(|account| names.insert(account.name.clone()))
+        {
+            return AlreadyExisting.fail();
         }
+
+        // when not given, only the current name is known
+        for (pubkey, account) in &accounts {
+            history
+                .entry(*pubkey)
+                .or_default()
+                .record(account.name.clone(), account.updated_at);
+        }
+
+        Ok(Self {
+            agent: AccountsHandler {
+                pubkey_to_account: accounts,
+                names,
+                history,
+                ttl,
+            }
+            .spawn(),
+        })
     }
 
-    pub async fn put(&self, pubkey: sign::PublicKey, name: Name) -> Result<(), Error> {
+    /// Register or update, returning the resulting account
+    ///
+    /// The time it was signed at is used as the time of the change, failing
+    /// with [`Error::Outdated`] if older than the latest accepted one.
+    pub async fn put(
+        &self,
+        pubkey: sign::PublicKey,
+        name: Name,
+        profile: Profile,
+        signed_at: SystemTime,
+    ) -> Result<Account, Error> {
         let (tx, rx) = oneshot::channel();
 
         self.agent
             .send(Commands::Put {
                 pubkey: Box::new(pubkey),
                 name,
+                profile,
+                signed_at,
                 resp: tx,
             })
             .await
@@ -54,7 +207,30 @@ impl Accounts {
         rx.await.map_err(|_| Error::GoneOnRecv)?
     }
 
-    pub async fn get_all(&self) -> Result<HashMap<sign::PublicKey, Name>, Error> {
+    /// Put many accounts at once, returning the result of each
+    ///
+    /// If atomic, either every put is accepted or none, the others then failing with
+    /// [`Error::Aborted`].
+    pub async fn put_batch(
+        &self,
+        puts: Vec<(sign::PublicKey, Name, Profile, SystemTime)>,
+        atomic: bool,
+    ) -> Result<Vec<Result<Account, Error>>, Error> {
+        let (tx, rx) = oneshot::channel();
+
+        self.agent
+            .send(Commands::PutBatch {
+                puts,
+                atomic,
+                resp: tx,
+            })
+            .await
+            .map_err(|_| Error::GoneOnSend)?;
+
+        rx.await.map_err(|_| Error::GoneOnRecv)
+    }
+
+    pub async fn get_all(&self) -> Result<HashSet<Contact>, Error> {
         let (tx, rx) = oneshot::channel();
 
         self.agent
@@ -64,18 +240,115 @@ impl Accounts {
 
         rx.await.map_err(|_| Error::GoneOnRecv)
     }
+
+    /// Get all the accounts, with their metadata and history
+    pub async fn snapshot(&self) -> Result<State, Error> {
+        let (tx, rx) = oneshot::channel();
+
+        self.agent
+            .send(Commands::Snapshot { resp: tx })
+            .await
+            .map_err(|_| Error::GoneOnSend)?;
+
+        rx.await.map_err(|_| Error::GoneOnRecv)
+    }
+
+    /// Merge accounts known by another instance, with the names they used.
+    ///
+    /// Histories are merged as is, even if the account is then dropped.
+    /// For a given public key, the most recently updated account wins.
+    /// If two public keys claim the same name, the earliest claim keeps it, ties
+    /// being broken by the public keys. The other account is dropped and has
+    /// to register again. Changing only the profile doesn't make a claim more
+    /// recent, nor does renewing it.
+    pub async fn merge(
+        &self,
+        accounts: Vec<(sign::PublicKey, Account, History)>,
+    ) -> Result<(), Error> {
+        let (tx, rx) = oneshot::channel();
+
+        self.agent
+            .send(Commands::Merge { accounts, resp: tx })
+            .await
+            .map_err(|_| Error::GoneOnSend)?;
+
+        rx.await.map_err(|_| Error::GoneOnRecv)
+    }
+
+    /// Get the names used by a public key, even if it was since dropped
+    pub async fn history(&self, pubkey: sign::PublicKey) -> Result<History, Error> {
+        let (tx, rx) = oneshot::channel();
+
+        self.agent
+            .send(Commands::History {
+                pubkey: Box::new(pubkey),
+                resp: tx,
+            })
+            .await
+            .map_err(|_| Error::GoneOnSend)?;
+
+        rx.await.map_err(|_| Error::GoneOnRecv)
+    }
+
+    /// Drop the expired accounts, freeing their names, returning their public keys
+    pub async fn sweep(&self) -> Result<Vec<sign::PublicKey>, Error> {
+        let (tx, rx) = oneshot::channel();
+
+        self.agent
+            .send(Commands::Sweep { resp: tx })
+            .await
+            .map_err(|_| Error::GoneOnSend)?;
+
+        rx.await.map_err(|_| Error::GoneOnRecv)
+    }
+
+    /// Mark an account as official or not, unless more recently attested
+    pub async fn attest(
+        &self,
+        pubkey: sign::PublicKey,
+        verification: Verification,
+    ) -> Result<(), Error> {
+        let (tx, rx) = oneshot::channel();
+
+        self.agent
+            .send(Commands::Attest {
+                pubkey: Box::new(pubkey),
+                verification,
+                resp: tx,
+            })
+            .await
+            .map_err(|_| Error::GoneOnSend)?;
+
+        rx.await.map_err(|_| Error::GoneOnRecv)?
+    }
+
+    /// Stop accepting commands, returning the accounts once the already sent ones are handled
+    pub async fn close(&self) -> Result<State, Error> {
+        let (tx, rx) = oneshot::channel();
+
+        self.agent
+            .send(Commands::Close { resp: tx })
+            .await
+            .map_err(|_| Error::GoneOnSend)?;
+
+        rx.await.map_err(|_| Error::GoneOnRecv)
+    }
 }
 
 struct AccountsHandler {
-    pubkey_to_name: HashMap<sign::PublicKey, Name>,
+    pubkey_to_account: HashMap<sign::PublicKey, Account>,
     names: HashSet<Name>,
+    history: HashMap<sign::PublicKey, History>,
+    ttl: Option<Duration>,
 }
 
 impl AccountsHandler {
-    fn new() -> Self {
+    fn new(ttl: Option<Duration>) -> Self {
         Self {
-            pubkey_to_name: Default::default(),
+            pubkey_to_account: Default::default(),
             names: Default::default(),
+            history: Default::default(),
+            ttl,
         }
     }
 
@@ -83,60 +356,335 @@ impl AccountsHandler {
         let (tx, mut rx) = mpsc::channel(32);
 
         tokio::spawn(async move {
+            let mut closed_by = Vec::new();
+
             while let Some(cmd) = rx.recv().await {
                 match cmd {
-                    Commands::Put { pubkey, name, resp } => {
-                        use std::collections::hash_map::Entry;
+                    Commands::Put {
+                        pubkey,
+                        name,
+                        profile,
+                        signed_at,
+                        resp,
+                    } => {
+                        let _ = resp.send(self.put(*pubkey, name, profile, signed_at));
 
-                        let _ = resp.send(match self.pubkey_to_name.entry(*pubkey) {
-                            // nobody claimed the name
-                            Entry::Vacant(entry) if !self.names.contains(&name) => {
-                                entry.insert(name.clone());
-                                self.names.insert(name.clone());
+                        debug_assert!(self.is_consistent())
+                    }
+                    Commands::PutBatch { puts, atomic, resp } => {
+                        // only the atomic batches can be rolled back
+                        let backup = atomic.then(|| {
+                            (
+                                self.pubkey_to_account.clone(),
+                                self.names.clone(),
+                                self.history.clone(),
+                            )
+                        });
 
-                                Ok(())
-                            }
-                            // same association already existing
-                            Entry::Occupied(existing) if existing.get() == &name => Ok(()),
+                        let mut results = puts
+                            .into_iter()
+                            .map(|(pubkey, name, profile, signed_at)| {
+                                self.put(pubkey, name, profile, signed_at)
+                            })
+                            .collect::<Vec<_>>();
 
-                            // someone already claimed the name
-                            Entry::Occupied(_) if self.names.contains(&name) => {
-                                AlreadyExisting.fail()
-                            }
-                            Entry::Vacant(_) => AlreadyExisting.fail(),
+                        if let Some((pubkey_to_account, names, history)) =
+                            backup.filter(|_| results.iter().any(Result::is_err))
+                        {
+                            self.pubkey_to_account = pubkey_to_account;
+                            self.names = names;
+                            self.history = history;
+
+                            results = results
+                                .into_iter()
+                                .map(|result| result.and_then(|_| Aborted.fail()))
+                                .collect();
+                        }
+
+                        debug_assert!(self.is_consistent());
+
+                        let _ = resp.send(results);
+                    }
+                    Commands::GetAll { resp } => {
+                        let _ = resp.send(
+                            self.pubkey_to_account
+                                .iter()
+                                .map(|(pubkey, account)| {
+                                    Contact::new(account.name.clone(), *pubkey)
+                                        .with_profile(account.profile.clone())
+                                        .with_verified(account.is_verified())
+                                })
+                                .collect(),
+                        );
+                    }
+                    Commands::Snapshot { resp } => {
+                        let _ = resp.send(self.state());
+                    }
+                    Commands::Merge { accounts, resp } => {
+                        let now = SystemTime::now();
+
+                        accounts.into_iter().for_each(|(pubkey, account, history)| {
+                            self.merge(pubkey, account, history, now)
+                        });
 
-                            // changing its name
-                            Entry::Occupied(mut entry) => {
-                                self.names.remove(entry.get());
-                                self.names.insert(name.clone());
-                                entry.insert(name.clone());
+                        debug_assert!(self.is_consistent());
 
+                        let _ = resp.send(());
+                    }
+                    Commands::History { pubkey, resp } => {
+                        let _ = resp.send(self.history.get(&pubkey).cloned().unwrap_or_default());
+                    }
+                    Commands::Attest {
+                        pubkey,
+                        verification,
+                        resp,
+                    } => {
+                        let _ = resp.send(match self.pubkey_to_account.get_mut(&pubkey) {
+                            Some(account) => {
+                                account.verification =
+                                    latest(account.verification, Some(verification));
                                 Ok(())
                             }
+                            None => NotFound.fail(),
                         });
+                    }
+                    Commands::Sweep { resp } => {
+                        let _ = resp.send(self.sweep(SystemTime::now()));
 
-                        debug_assert!({
-                            let mut names = self.names.clone();
-                            self.pubkey_to_name.values().all(|name| names.remove(name))
-                        })
+                        debug_assert!(self.is_consistent());
                     }
-                    Commands::GetAll { resp } => {
-                        let _ = resp.send(self.pubkey_to_name.clone());
+                    Commands::Close { resp } => {
+                        // still yields the commands already queued
+                        rx.close();
+                        closed_by.push(resp);
                     }
                 }
             }
+
+            for resp in closed_by {
+                let _ = resp.send(self.state());
+            }
         });
 
         tx
     }
+
+    fn put(
+        &mut self,
+        pubkey: sign::PublicKey,
+        name: Name,
+        profile: Profile,
+        now: SystemTime,
+    ) -> Result<Account, Error> {
+        use std::collections::hash_map::Entry;
+
+        // otherwise, an older claim could be replayed to undo a change
+        if let Some(account) = self.pubkey_to_account.get(&pubkey) {
+            if now < account.renewed_at {
+                return Outdated.fail();
+            }
+        }
+
+        match self.pubkey_to_account.entry(pubkey) {
+            // nobody claimed the name
+            Entry::Vacant(entry) if !self.names.contains(&name) => {
+                let account = entry.insert(Account {
+                    name: name.clone(),
+                    profile,
+                    registered_at: now,
+                    updated_at: now,
+                    renewed_at: now,
+                    verification: None,
+                });
+                self.names.insert(name.clone());
+                self.history.entry(pubkey).or_default().record(name, now);
+
+                Ok(account.clone())
+            }
+            // same association already existing
+            Entry::Occupied(mut existing) if existing.get().name == name => {
+                let account = existing.get_mut();
+
+                if account.profile != profile {
+                    account.profile = profile;
+                    account.updated_at = now;
+                }
+                account.renewed_at = now;
+
+                Ok(account.clone())
+            }
+
+            // someone already claimed the name
+            Entry::Occupied(_) if self.names.contains(&name) => AlreadyExisting.fail(),
+            Entry::Vacant(_) => AlreadyExisting.fail(),
+
+            // changing its name
+            Entry::Occupied(mut entry) => {
+                let account = entry.get_mut();
+
+                self.names.remove(&account.name);
+                self.names.insert(name.clone());
+                account.name = name.clone();
+                account.profile = profile;
+                account.updated_at = now;
+                account.renewed_at = now;
+                self.history.entry(pubkey).or_default().record(name, now);
+
+                Ok(account.clone())
+            }
+        }
+    }
+
+    fn is_expired(&self, account: &Account, now: SystemTime) -> bool {
+        self.ttl
+            .map_or(false, |ttl| account.renewed_at + ttl <= now)
+    }
+
+    fn sweep(&mut self, now: SystemTime) -> Vec<sign::PublicKey> {
+        let expired = self
+            .pubkey_to_account
+            .iter()
+            .filter(|(_, account)| self.is_expired(account, now))
+            .map(|(pubkey, _)| *pubkey)
+            .collect::<Vec<_>>();
+
+        // history is kept, to still know who was behind old transactions
+        for pubkey in &expired {
+            if let Some(account) = self.pubkey_to_account.remove(pubkey) {
+                self.names.remove(&account.name);
+            }
+        }
+
+        expired
+    }
+
+    fn state(&self) -> State {
+        State {
+            accounts: self.pubkey_to_account.clone(),
+            history: self.history.clone(),
+        }
+    }
+
+    fn merge(
+        &mut self,
+        pubkey: sign::PublicKey,
+        mut account: Account,
+        history: History,
+        now: SystemTime,
+    ) {
+        let local_history = self.history.entry(pubkey).or_default();
+        for change in history.changes() {
+            local_history.record(change.name.clone(), change.since);
+        }
+
+        // otherwise, an instance not yet swept would give it back
+        if self.is_expired(&account, now) {
+            return;
+        }
+
+        if let Some(local) = self.pubkey_to_account.get_mut(&pubkey) {
+            // renewing doesn't change the account, so it is merged separately
+            let renewed_at = local.renewed_at.max(account.renewed_at);
+            local.renewed_at = renewed_at;
+            account.renewed_at = renewed_at;
+            // same for attestations
+            let verification = latest(local.verification, account.verification);
+            local.verification = verification;
+            account.verification = verification;
+
+            account.registered_at = local.registered_at.min(account.registered_at);
+            // an unchanged claim only renews it, as when shared again via the ledger
+            if (&local.name, &local.profile) == (&account.name, &account.profile) {
+                account.updated_at = local.updated_at;
+            }
+
+            if (local.updated_at, &local.name) >= (account.updated_at, &account.name) {
+                return;
+            }
+        }
+
+        let owner = self
+            .pubkey_to_account
+            .iter()
+            .find(|(owner, owned)| owned.name == account.name && **owner != pubkey);
+        let loser = match owner {
+            // public keys are only compared to deterministically break ties
+            Some((owner, owned))
+                if (self.named_since(owner, owned), owner.to_string())
+                    <= (self.named_since(&pubkey, &account), pubkey.to_string()) =>
+            {
+                return
+            }
+            Some((owner, _)) => Some(*owner),
+            None => None,
+        };
+
+        if let Some(loser) = loser {
+            self.pubkey_to_account.remove(&loser);
+            self.names.remove(&account.name);
+        }
+
+        self.history
+            .entry(pubkey)
+            .or_default()
+            .record(account.name.clone(), account.updated_at);
+
+        let name = account.name.clone();
+        if let Some(previous) = self.pubkey_to_account.insert(pubkey, account) {
+            self.names.remove(&previous.name);
+        }
+        self.names.insert(name);
+    }
+
+    /// When the current name of a public key was taken
+    fn named_since(&self, pubkey: &sign::PublicKey, account: &Account) -> SystemTime {
+        // changing only the profile isn't recorded in the history
+        self.history
+            .get(pubkey)
+            .and_then(|history| history.changes().last())
+            .filter(|change| change.name == account.name)
+            .map_or(account.updated_at, |change| change.since)
+    }
+
+    /// Names and public keys are in bijection
+    fn is_consistent(&self) -> bool {
+        let mut names = self.names.clone();
+
+        self.pubkey_to_account
+            .values()
+            .all(|account| names.remove(&account.name))
+            && names.is_empty()
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use at2_ns::User;
-    use drop::crypto::sign::KeyPair;
+    use std::{
+        collections::{HashMap, HashSet},
+        time::{Duration, SystemTime},
+    };
+
+    use at2_ns::{History, NameChange, Profile, User};
+    use drop::crypto::sign::{self, KeyPair};
+    use proptest::prelude::*;
+    use tokio::runtime::{self, Runtime};
+
+    use super::{Account, Accounts, Contact, Error, Verification};
 
-    use super::Accounts;
+    const TTL: Duration = Duration::from_secs(60);
+
+    fn expired_account(name: &str) -> Account {
+        let long_ago = SystemTime::now() - 2 * TTL;
+
+        Account {
+            name: name.to_owned(),
+            profile: Profile::default(),
+            registered_at: long_ago,
+            updated_at: long_ago,
+            renewed_at: long_ago,
+            verification: None,
+        }
+    }
 
     #[tokio::test]
     async fn put_once_returns_it_in_get_all() {
@@ -144,7 +692,12 @@ mod tests {
         let user = User::new("user".to_owned(), KeyPair::random());
 
         accounts
-            .put(user.public_key(), user.name.clone())
+            .put(
+                user.public_key(),
+                user.name.clone(),
+                Profile::default(),
+                SystemTime::now(),
+            )
             .await
             .expect("put user");
 
@@ -155,7 +708,7 @@ mod tests {
                 .expect("get all")
                 .into_iter()
                 .collect::<Vec<_>>(),
-            vec![(user.public_key(), "user".to_owned())],
+            vec![user.to_thin()],
         );
     }
 
@@ -165,14 +718,92 @@ mod tests {
         let user = User::new("user".to_owned(), KeyPair::random());
 
         accounts
-            .put(user.public_key(), "first".to_owned())
+            .put(
+                user.public_key(),
+                "first".to_owned(),
+                Profile::default(),
+                SystemTime::now(),
+            )
             .await
             .expect("first put");
         accounts
-            .put(user.public_key(), "second".to_owned())
+            .put(
+                user.public_key(),
+                "second".to_owned(),
+                Profile::default(),
+                SystemTime::now(),
+            )
+            .await
+            .expect("second put");
+
+        assert_eq!(
+            accounts
+                .get_all()
+                .await
+                .expect("get all")
+                .into_iter()
+                .collect::<Vec<_>>(),
+            vec![Contact::new("second".to_owned(), user.public_key())],
+        );
+    }
+
+    #[tokio::test]
+    async fn rename_is_kept_in_history() {
+        let accounts = Accounts::new();
+        let user = User::new("user".to_owned(), KeyPair::random());
+
+        let first = accounts
+            .put(
+                user.public_key(),
+                "first".to_owned(),
+                Profile::default(),
+                SystemTime::now(),
+            )
+            .await
+            .expect("first put");
+        let second = accounts
+            .put(
+                user.public_key(),
+                "second".to_owned(),
+                Profile::default(),
+                SystemTime::now(),
+            )
+            .await
+            .expect("second put");
+
+        let history = accounts.history(user.public_key()).await.expect("history");
+        assert_eq!(history.name_at(first.updated_at), Some("first"));
+        assert_eq!(history.name_at(second.updated_at), Some("second"));
+        assert_eq!(history.changes().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn put_same_name_updates_profile() {
+        let accounts = Accounts::new();
+        let mut user = User::new("user".to_owned(), KeyPair::random());
+
+        let first = accounts
+            .put(
+                user.public_key(),
+                user.name.clone(),
+                Profile::default(),
+                SystemTime::now(),
+            )
+            .await
+            .expect("first put");
+
+        user.profile.bio = Some("hello".to_owned());
+        let second = accounts
+            .put(
+                user.public_key(),
+                user.name.clone(),
+                user.profile.clone(),
+                SystemTime::now(),
+            )
             .await
             .expect("second put");
 
+        assert!(second.updated_at > first.updated_at);
         assert_eq!(
             accounts
                 .get_all()
@@ -180,7 +811,7 @@ mod tests {
                 .expect("get all")
                 .into_iter()
                 .collect::<Vec<_>>(),
-            vec![(user.public_key(), "second".to_owned())],
+            vec![user.to_thin()],
         );
     }
 
@@ -192,15 +823,55 @@ mod tests {
         let second_user = User::new("user".to_owned(), KeyPair::random());
 
         accounts
-            .put(first_user.public_key(), first_user.name.clone())
+            .put(
+                first_user.public_key(),
+                first_user.name.clone(),
+                Profile::default(),
+                SystemTime::now(),
+            )
             .await
             .expect("put first user");
         accounts
-            .put(second_user.public_key(), second_user.name.clone())
+            .put(
+                second_user.public_key(),
+                second_user.name.clone(),
+                Profile::default(),
+                SystemTime::now(),
+            )
             .await
             .expect_err("fail to put second user");
     }
 
+    #[tokio::test]
+    async fn restore_from_snapshot() {
+        let accounts = Accounts::new();
+        let user = User::new("user".to_owned(), KeyPair::random());
+
+        accounts
+            .put(
+                user.public_key(),
+                user.name.clone(),
+                Profile::default(),
+                SystemTime::now(),
+            )
+            .await
+            .expect("put user");
+
+        let snapshot = accounts.snapshot().await.expect("snapshot");
+        let restored = Accounts::restore(snapshot.clone(), None).expect("restore");
+
+        assert_eq!(restored.snapshot().await.expect("snapshot"), snapshot);
+        restored
+            .put(
+                KeyPair::random().public(),
+                "user".to_owned(),
+                Profile::default(),
+                SystemTime::now(),
+            )
+            .await
+            .expect_err("name is still taken");
+    }
+
     #[tokio::test]
     async fn update_name_for_another_already_existing() {
         let accounts = Accounts::new();
@@ -209,17 +880,351 @@ mod tests {
         let second_user = User::new("usr".to_owned(), KeyPair::random());
 
         accounts
-            .put(first_user.public_key(), first_user.name.clone())
+            .put(
+                first_user.public_key(),
+                first_user.name.clone(),
+                Profile::default(),
+                SystemTime::now(),
+            )
             .await
             .expect("put first user");
         accounts
-            .put(second_user.public_key(), second_user.name.clone())
+            .put(
+                second_user.public_key(),
+                second_user.name.clone(),
+                Profile::default(),
+                SystemTime::now(),
+            )
             .await
             .expect("put second user");
 
         accounts
-            .put(second_user.public_key(), "user".to_owned())
+            .put(
+                second_user.public_key(),
+                "user".to_owned(),
+                Profile::default(),
+                SystemTime::now(),
+            )
             .await
             .expect_err("fail to update name for second user");
     }
+
+    #[tokio::test]
+    async fn expired_name_can_be_reclaimed() {
+        let squatter = KeyPair::random().public();
+        let accounts = Accounts::restore(
+            vec![(squatter, expired_account("user"))]
+                .into_iter()
+                .collect(),
+            Some(TTL),
+        )
+        .expect("restore");
+
+        assert_eq!(accounts.sweep().await.expect("sweep"), vec![squatter]);
+
+        let user = User::new("user".to_owned(), KeyPair::random());
+        accounts
+            .put(
+                user.public_key(),
+                user.name.clone(),
+                Profile::default(),
+                SystemTime::now(),
+            )
+            .await
+            .expect("put user");
+        let history = accounts.history(squatter).await.expect("history");
+        assert_eq!(history.current(), Some("user"));
+    }
+
+    #[tokio::test]
+    async fn put_renews_account() {
+        let user = User::new("user".to_owned(), KeyPair::random());
+        let accounts = Accounts::restore(
+            vec![(user.public_key(), expired_account("user"))]
+                .into_iter()
+                .collect(),
+            Some(TTL),
+        )
+        .expect("restore");
+
+        accounts
+            .put(
+                user.public_key(),
+                user.name.clone(),
+                Profile::default(),
+                SystemTime::now(),
+            )
+            .await
+            .expect("renew user");
+
+        assert_eq!(accounts.sweep().await.expect("sweep"), vec![]);
+        assert_eq!(
+            accounts
+                .get_all()
+                .await
+                .expect("get all")
+                .into_iter()
+                .collect::<Vec<_>>(),
+            vec![user.to_thin()],
+        );
+    }
+
+    #[tokio::test]
+    async fn merge_keeps_older_names() {
+        let accounts = Accounts::new();
+        let pubkey = KeyPair::random().public();
+        let now = SystemTime::now();
+        let mut account = expired_account("second");
+        account.updated_at = now;
+
+        let history = vec![
+            NameChange {
+                name: "first".to_owned(),
+                since: now - Duration::from_secs(1),
+            },
+            NameChange {
+                name: "second".to_owned(),
+                since: now,
+            },
+        ]
+        .into_iter()
+        .collect::<History>();
+        accounts
+            .merge(vec![(pubkey, account, history.clone())])
+            .await
+            .expect("merge");
+
+        assert_eq!(accounts.history(pubkey).await.expect("history"), history);
+    }
+
+    #[tokio::test]
+    async fn merge_ignores_expired_accounts() {
+        let accounts = Accounts::with_ttl(Some(TTL));
+
+        accounts
+            .merge(vec![(
+                KeyPair::random().public(),
+                expired_account("user"),
+                History::default(),
+            )])
+            .await
+            .expect("merge");
+
+        assert!(accounts.get_all().await.expect("get all").is_empty());
+    }
+
+    #[tokio::test]
+    async fn without_ttl_nothing_expires() {
+        let pubkey = KeyPair::random().public();
+        let accounts = Accounts::restore(
+            vec![(pubkey, expired_account("user"))]
+                .into_iter()
+                .collect(),
+            None,
+        )
+        .expect("restore");
+
+        assert_eq!(accounts.sweep().await.expect("sweep"), vec![]);
+    }
+
+    #[tokio::test]
+    async fn close_handles_pending_commands() {
+        let accounts = Accounts::new();
+        let user = User::new("user".to_owned(), KeyPair::random());
+
+        let (put, closed) = tokio::join!(
+            accounts.put(
+                user.public_key(),
+                user.name.clone(),
+                Profile::default(),
+                SystemTime::now()
+            ),
+            accounts.close(),
+        );
+        put.expect("put before close");
+        let closed = closed.expect("close");
+
+        assert_eq!(
+            closed
+                .accounts
+                .get(&user.public_key())
+                .map(|account| &account.name),
+            Some(&user.name)
+        );
+        assert!(matches!(accounts.get_all().await, Err(Error::GoneOnSend)));
+    }
+
+    #[tokio::test]
+    async fn only_latest_attestation_counts() {
+        let accounts = Accounts::new();
+        let user = User::new("user".to_owned(), KeyPair::random());
+        let now = SystemTime::now();
+
+        accounts
+            .attest(
+                user.public_key(),
+                Verification {
+                    verified: true,
+                    issued_at: now,
+                },
+            )
+            .await
+            .expect_err("not yet registered");
+
+        accounts
+            .put(
+                user.public_key(),
+                user.name.clone(),
+                Profile::default(),
+                SystemTime::now(),
+            )
+            .await
+            .expect("put user");
+        for (verified, issued_at) in [(true, now), (false, now - TTL)] {
+            accounts
+                .attest(
+                    user.public_key(),
+                    Verification {
+                        verified,
+                        issued_at,
+                    },
+                )
+                .await
+                .expect("attest");
+        }
+
+        assert_eq!(
+            accounts
+                .get_all()
+                .await
+                .expect("get all")
+                .into_iter()
+                .collect::<Vec<_>>(),
+            vec![user.to_thin().with_verified(true)],
+        );
+    }
+
+    const KEYS: usize = 8;
+    const NAMES: usize = 6;
+
+    /// Puts as indexes of keys and names, few enough to often conflict
+    fn puts() -> impl Strategy<Value = Vec<(usize, usize)>> {
+        prop::collection::vec((0..KEYS, 0..NAMES), 1..64)
+    }
+
+    fn keys() -> Vec<sign::PublicKey> {
+        (0..KEYS).map(|_| KeyPair::random().public()).collect()
+    }
+
+    fn name(index: usize) -> String {
+        format!("user{}", index)
+    }
+
+    fn runtime() -> Runtime {
+        runtime::Builder::new_multi_thread()
+            .enable_all()
+            .build()
+            .expect("runtime")
+    }
+
+    /// Panic if a name is used by more than one public key
+    fn bijection(contacts: HashSet<Contact>) -> HashMap<sign::PublicKey, String> {
+        let names = contacts
+            .iter()
+            .map(|contact| contact.name.clone())
+            .collect::<HashSet<_>>();
+        assert_eq!(names.len(), contacts.len(), "name used twice");
+
+        contacts
+            .into_iter()
+            .map(|contact| (*contact.public_key(), contact.name))
+            .collect()
+    }
+
+    proptest! {
+        #[test]
+        fn sequential_puts_follow_first_come_first_served(puts in puts()) {
+            runtime().block_on(async {
+                let (accounts, keys) = (Accounts::new(), keys());
+                let mut expected = HashMap::new();
+
+                for (key, name) in puts.into_iter().map(|(key, index)| (keys[key], name(index))) {
+                    let taken = expected
+                        .iter()
+                        .any(|(owner, used)| *owner != key && *used == name);
+
+                    let put = accounts.put(key, name.clone(), Profile::default(), SystemTime::now()).await;
+                    prop_assert_eq!(put.is_ok(), !taken);
+
+                    if !taken {
+                        expected.insert(key, name);
+                    }
+                }
+
+                prop_assert_eq!(bijection(accounts.get_all().await.expect("get all")), expected);
+
+                Ok(())
+            })?;
+        }
+
+        #[test]
+        fn concurrent_puts_keep_the_bijection(puts in puts()) {
+            runtime().block_on(async {
+                let (accounts, keys) = (Accounts::new(), keys());
+
+                let reader = {
+                    let accounts = accounts.clone();
+                    tokio::spawn(async move {
+                        for _ in 0..16 {
+                            bijection(accounts.get_all().await.expect("get all"));
+                            tokio::task::yield_now().await;
+                        }
+                    })
+                };
+
+                let putters = puts
+                    .into_iter()
+                    .map(|(key, index)| {
+                        let (accounts, key, name) = (accounts.clone(), keys[key], name(index));
+                        tokio::spawn(async move {
+                            accounts
+                                .put(key, name.clone(), Profile::default(), SystemTime::now())
+                                .await
+                                .ok()
+                                .map(|_| (key, name))
+                        })
+                    })
+                    .collect::<Vec<_>>();
+
+                let mut accepted = HashMap::<_, HashSet<_>>::new();
+                for putter in putters {
+                    if let Some((key, name)) = putter.await.expect("putter") {
+                        accepted.entry(key).or_default().insert(name);
+                    }
+                }
+                reader.await.expect("reader");
+
+                let current = bijection(accounts.get_all().await.expect("get all"));
+                prop_assert_eq!(
+                    current.keys().collect::<HashSet<_>>(),
+                    accepted.keys().collect::<HashSet<_>>()
+                );
+                for (key, name) in &current {
+                    prop_assert!(accepted[key].contains(name), "{} never accepted", name);
+                }
+
+                let snapshot = accounts
+                    .snapshot()
+                    .await
+                    .expect("snapshot")
+                    .accounts
+                    .into_iter()
+                    .map(|(key, account)| (key, account.name))
+                    .collect::<HashMap<_, _>>();
+                prop_assert_eq!(snapshot, current);
+
+                Ok(())
+            })?;
+        }
+    }
 }
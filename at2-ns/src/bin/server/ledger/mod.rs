@@ -0,0 +1,338 @@
+//! Share name claims through an AT2-compatible broadcast
+//!
+//! Instead of trusting each server, every claim accepted by a node is
+//! broadcast to the others, which check its signature before merging it in
+//! their own directory. As the time of the claim is signed by the user, a node
+//! can neither backdate it to take a name, nor replay an old one to undo a
+//! change.
+
+use std::{error::Error as StdError, sync::Arc, time::SystemTime};
+
+use at2_ns::{History, Profile};
+use drop::crypto::sign;
+use serde::{Deserialize, Serialize};
+use snafu::ResultExt;
+
+use super::accounts::{self, Account, Accounts};
+
+pub mod sieve;
+
+pub type BroadcastError = Box<dyn StdError + Send + Sync>;
+
+#[derive(snafu::Snafu, Debug)]
+pub enum Error {
+    #[snafu(display("broadcast: {}", source))]
+    Broadcast { source: BroadcastError },
+}
+
+/// Name claim, as signed by the user
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Claim {
+    pub public_key: sign::PublicKey,
+    pub name: String,
+    pub profile: Profile,
+    /// Signature of the name, profile and time by the public key
+    pub signature: sign::Signature,
+    pub signed_at: SystemTime,
+}
+
+/// Layer sharing the claims between nodes
+#[tonic::async_trait]
+pub trait Broadcast: Send + Sync + 'static {
+    /// Send a claim to every node
+    async fn broadcast(&self, claim: &Claim) -> Result<(), BroadcastError>;
+
+    /// Wait for the next claim, `None` if the layer is gone
+    async fn deliver(&self) -> Option<Claim>;
+}
+
+#[derive(Clone)]
+pub struct Ledger {
+    broadcast: Arc<dyn Broadcast>,
+}
+
+impl Ledger {
+    /// Merge every delivered claim in the given accounts
+    pub fn start(accounts: Accounts, broadcast: Arc<dyn Broadcast>) -> Self {
+        let delivering = broadcast.clone();
+
+        tokio::spawn(async move {
+            while let Some(claim) = delivering.deliver().await {
+                if !claim.profile.is_valid() {
+                    tracing::warn!("dropping claim for {}: invalid profile", claim.name);
+                    continue;
+                }
+                // otherwise, an old claim could be replayed
+                if !accounts::is_recent(claim.signed_at, SystemTime::now()) {
+                    tracing::warn!("dropping claim for {}: not signed recently", claim.name);
+                    continue;
+                }
+                if let Err(err) = claim.profile.verify(
+                    &claim.signature,
+                    &claim.public_key,
+                    &claim.name,
+                    claim.signed_at,
+                ) {
+                    tracing::warn!("dropping claim for {}: {}", claim.name, err);
+                    continue;
+                }
+
+                let account = Account {
+                    name: claim.name,
+                    profile: claim.profile,
+                    registered_at: claim.signed_at,
+                    updated_at: claim.signed_at,
+                    renewed_at: claim.signed_at,
+                    // attestations aren't shared via the ledger
+                    verification: None,
+                };
+                // only recorded if accepted
+                let history = History::default();
+                if let Err(err) = accounts
+                    .merge(vec![(claim.public_key, account, history)])
+                    .await
+                {
+                    tracing::error!("unable to merge claim: {}", err);
+                    break;
+                }
+            }
+        });
+
+        Self { broadcast }
+    }
+
+    /// Share a claim already accepted locally
+    pub async fn share(&self, claim: Claim) -> Result<(), Error> {
+        self.broadcast.broadcast(&claim).await.context(Broadcast)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::Arc,
+        time::{Duration, SystemTime, UNIX_EPOCH},
+    };
+
+    use at2_ns::{
+        proto::{self, name_service_server::NameService},
+        User,
+    };
+    use drop::crypto::sign::KeyPair;
+    use tokio::{
+        sync::{broadcast, Mutex},
+        time,
+    };
+
+    use super::{Broadcast, BroadcastError, Claim, Ledger};
+    use crate::{accounts::Accounts, rpc::Service};
+
+    /// Every node receives every claim, in the same order
+    struct LocalBroadcast {
+        sender: broadcast::Sender<Claim>,
+        receiver: Mutex<broadcast::Receiver<Claim>>,
+    }
+
+    #[tonic::async_trait]
+    impl Broadcast for LocalBroadcast {
+        async fn broadcast(&self, claim: &Claim) -> Result<(), BroadcastError> {
+            self.sender.send(claim.clone())?;
+            Ok(())
+        }
+
+        async fn deliver(&self) -> Option<Claim> {
+            self.receiver.lock().await.recv().await.ok()
+        }
+    }
+
+    struct Node {
+        accounts: Accounts,
+        broadcast: Arc<dyn Broadcast>,
+        service: Service,
+    }
+
+    fn network(count: usize) -> Vec<Node> {
+        let (sender, _) = broadcast::channel(64);
+
+        (0..count)
+            .map(|_| {
+                let accounts = Accounts::new();
+                let broadcast: Arc<dyn Broadcast> = Arc::new(LocalBroadcast {
+                    sender: sender.clone(),
+                    receiver: Mutex::new(sender.subscribe()),
+                });
+                let ledger = Ledger::start(accounts.clone(), broadcast.clone());
+
+                Node {
+                    service: Service::new(accounts.clone()).with_ledger(ledger),
+                    accounts,
+                    broadcast,
+                }
+            })
+            .collect()
+    }
+
+    fn claim(user: &User, signed_at: SystemTime) -> Claim {
+        Claim {
+            public_key: user.public_key(),
+            name: user.name.clone(),
+            profile: user.profile.clone(),
+            signature: user.sign_claim(signed_at).expect("sign"),
+            signed_at,
+        }
+    }
+
+    async fn eventually_all_have(nodes: &[Node], expected: &[&User]) {
+        let expected = expected
+            .iter()
+            .map(|user| (*user).clone().to_thin())
+            .collect();
+
+        for _ in 0..100 {
+            let mut converged = true;
+            for node in nodes {
+                converged &= node.accounts.get_all().await.expect("get all") == expected;
+            }
+
+            if converged {
+                return;
+            }
+
+            time::sleep(Duration::from_millis(10)).await;
+        }
+
+        panic!("nodes never converged to {:?}", expected);
+    }
+
+    #[tokio::test]
+    async fn put_is_shared_with_every_node() {
+        let nodes = network(3);
+        let mut user = User::new("user".to_owned(), KeyPair::random());
+        user.profile.bio = Some("hello".to_owned());
+        let signed_at = SystemTime::now();
+
+        nodes[0]
+            .service
+            .put(tonic::Request::new(proto::PutRequest {
+                account: Some(proto::Account {
+                    public_key: bincode::serialize(&user.public_key()).expect("serialize"),
+                    name: user.name.clone(),
+                    profile: Some(user.profile.clone().into()),
+                    verified: false,
+                }),
+                signature: bincode::serialize(&user.sign_claim(signed_at).expect("sign"))
+                    .expect("serialize"),
+                signed_at: at2_ns::to_timestamp(signed_at),
+            }))
+            .await
+            .expect("put user");
+
+        eventually_all_have(&nodes, &[&user]).await;
+    }
+
+    #[tokio::test]
+    async fn forged_claim_is_dropped() {
+        let nodes = network(2);
+        let (user, forger) = (
+            User::new("user".to_owned(), KeyPair::random()),
+            User::new("user".to_owned(), KeyPair::random()),
+        );
+
+        let mut forged = claim(&forger, SystemTime::now());
+        forged.public_key = user.public_key();
+        nodes[0]
+            .broadcast
+            .broadcast(&forged)
+            .await
+            .expect("broadcast");
+
+        let other = User::new("other".to_owned(), KeyPair::random());
+        nodes[0]
+            .broadcast
+            .broadcast(&claim(&other, SystemTime::now()))
+            .await
+            .expect("broadcast");
+
+        // delivered in order, so the forged one was already processed
+        eventually_all_have(&nodes, &[&other]).await;
+    }
+
+    #[tokio::test]
+    async fn concurrent_claims_keep_the_earliest() {
+        let nodes = network(2);
+        let now = SystemTime::now();
+        let (first, second) = (
+            User::new("user".to_owned(), KeyPair::random()),
+            User::new("user".to_owned(), KeyPair::random()),
+        );
+
+        nodes[1]
+            .broadcast
+            .broadcast(&claim(&second, now + Duration::from_secs(1)))
+            .await
+            .expect("broadcast second");
+        nodes[0]
+            .broadcast
+            .broadcast(&claim(&first, now))
+            .await
+            .expect("broadcast first");
+
+        eventually_all_have(&nodes, &[&first]).await;
+    }
+
+    #[tokio::test]
+    async fn backdated_claim_is_dropped() {
+        let nodes = network(2);
+        let user = User::new("user".to_owned(), KeyPair::random());
+
+        nodes[0]
+            .broadcast
+            .broadcast(&claim(&user, UNIX_EPOCH))
+            .await
+            .expect("broadcast");
+
+        let mut tampered = claim(&user, SystemTime::now());
+        tampered.signed_at -= Duration::from_secs(1);
+        nodes[0]
+            .broadcast
+            .broadcast(&tampered)
+            .await
+            .expect("broadcast");
+
+        let other = User::new("other".to_owned(), KeyPair::random());
+        nodes[0]
+            .broadcast
+            .broadcast(&claim(&other, SystemTime::now()))
+            .await
+            .expect("broadcast");
+
+        // delivered in order, so the two others were already processed
+        eventually_all_have(&nodes, &[&other]).await;
+    }
+
+    #[tokio::test]
+    async fn replayed_claim_does_not_undo_a_rename() {
+        let nodes = network(2);
+        let now = SystemTime::now();
+        let mut user = User::new("old".to_owned(), KeyPair::random());
+
+        let old = claim(&user, now);
+        nodes[0].broadcast.broadcast(&old).await.expect("broadcast");
+        user.name = "new".to_owned();
+        nodes[0]
+            .broadcast
+            .broadcast(&claim(&user, now + Duration::from_secs(1)))
+            .await
+            .expect("broadcast");
+        nodes[1].broadcast.broadcast(&old).await.expect("broadcast");
+
+        let other = User::new("other".to_owned(), KeyPair::random());
+        nodes[0]
+            .broadcast
+            .broadcast(&claim(&other, now))
+            .await
+            .expect("broadcast");
+
+        eventually_all_have(&nodes, &[&user, &other]).await;
+    }
+}
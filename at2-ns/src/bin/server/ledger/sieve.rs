@@ -0,0 +1,109 @@
+//! Broadcast over sieve, as the AT2 nodes do
+
+use std::{fs, net::SocketAddr, path::Path};
+
+use drop::{
+    crypto::{
+        key::exchange::{self, Exchanger},
+        sign,
+    },
+    message,
+    net::{TcpConnector, TcpListener},
+    system::{manager::Handle, AllSampler, System, SystemManager},
+};
+use serde::Deserialize;
+use sieve::{Sieve, SieveConfig};
+use tokio::sync::Mutex;
+
+use super::{Broadcast, BroadcastError, Claim};
+
+/// Network configuration of a node, as JSON
+#[derive(Deserialize)]
+pub struct Config {
+    /// Where to listen for the other nodes
+    address: SocketAddr,
+    network_keypair: exchange::KeyPair,
+    sign_keypair: sign::KeyPair,
+    /// Other nodes of the network
+    peers: Vec<Peer>,
+}
+
+#[derive(Deserialize)]
+struct Peer {
+    public_key: exchange::PublicKey,
+    address: SocketAddr,
+}
+
+impl Config {
+    pub fn load(path: &Path) -> Result<Self, BroadcastError> {
+        Ok(serde_json::from_slice(&fs::read(path)?)?)
+    }
+}
+
+#[message]
+pub struct Payload {
+    claim: Claim,
+}
+
+pub struct SieveBroadcast<H> {
+    // handles are locked while waiting, so use one per direction
+    broadcasting: Mutex<H>,
+    delivering: Mutex<H>,
+}
+
+/// Join the sieve network described by the config
+pub async fn start(config: Config) -> Result<impl Broadcast, BroadcastError> {
+    let system = System::new_with_connector_zipped(
+        &config.network_keypair,
+        TcpConnector::new(config.network_keypair.clone()),
+        config
+            .peers
+            .into_iter()
+            .map(|peer| (peer.public_key, peer.address)),
+    )
+    .await;
+
+    let listener = TcpListener::new(config.address, Exchanger::new(config.network_keypair)).await?;
+
+    let handle = SystemManager::new(system)
+        .run(
+            Sieve::<Payload>::new(config.sign_keypair, SieveConfig::default()),
+            listener,
+            AllSampler::default(),
+        )
+        .await;
+
+    Ok(SieveBroadcast {
+        broadcasting: Mutex::new(handle.clone()),
+        delivering: Mutex::new(handle),
+    })
+}
+
+#[tonic::async_trait]
+impl<H> Broadcast for SieveBroadcast<H>
+where
+    H: Handle<Payload, Payload> + Send + 'static,
+    H::Error: std::error::Error + Send + Sync + 'static,
+{
+    async fn broadcast(&self, claim: &Claim) -> Result<(), BroadcastError> {
+        self.broadcasting
+            .lock()
+            .await
+            .broadcast(&Payload {
+                claim: claim.clone(),
+            })
+            .await?;
+
+        Ok(())
+    }
+
+    async fn deliver(&self) -> Option<Claim> {
+        match self.delivering.lock().await.deliver().await {
+            Ok(payload) => Some(payload.claim),
+            Err(err) => {
+                tracing::error!("sieve stopped delivering: {}", err);
+                None
+            }
+        }
+    }
+}
@@ -1,48 +1,335 @@
-use std::{net::SocketAddr, process};
+use std::{
+    collections::HashSet, fs, io, net::SocketAddr, path::PathBuf, process, str::FromStr, sync::Arc,
+    time::Duration,
+};
 
 use at2_ns::proto::name_service_server::NameServiceServer;
+use drop::crypto::sign;
+use http::Uri;
 use snafu::ResultExt;
 use structopt::StructOpt;
+use tokio::{
+    net::TcpListener,
+    signal::unix::{signal, SignalKind},
+    sync::oneshot,
+    time,
+};
 use tonic::transport::Server;
-use tracing::{subscriber, Level};
-use tracing_fmt::FmtSubscriber;
+use tracing_subscriber::EnvFilter;
 
 mod accounts;
+#[cfg(feature = "ledger")]
+mod ledger;
+mod metrics;
+mod replication;
 mod rpc;
+mod snapshot;
+mod tls;
+
+enum LogFormat {
+    Text,
+    Json,
+}
+
+impl FromStr for LogFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(Self::Text),
+            "json" => Ok(Self::Json),
+            _ => Err(format!("unknown log format: {}", s)),
+        }
+    }
+}
 
 #[derive(structopt::StructOpt)]
 struct Arguments {
     address: SocketAddr,
+
+    /// Which logs to show, as a level such as `warn` or per module, such as `server=debug`
+    #[structopt(long, env = "AT2_NS_LOG", default_value = "info")]
+    log: String,
+    /// How to write the logs
+    #[structopt(
+        long,
+        env = "AT2_NS_LOG_FORMAT",
+        default_value = "text",
+        possible_values = &["text", "json"],
+    )]
+    log_format: LogFormat,
+    /// Websites allowed to call the service, can be repeated, `*` for every one
+    #[structopt(
+        long = "allow-origin",
+        env = "AT2_NS_ALLOW_ORIGINS",
+        use_delimiter = true,
+        default_value = "https://factory.c4dt.org"
+    )]
+    allowed_origins: Vec<String>,
+
+    /// PEM encoded certificate chain to serve over HTTPS, reloaded on SIGHUP
+    #[structopt(long, requires = "tls-key")]
+    tls_certificate: Option<PathBuf>,
+    /// PEM encoded private key of the certificate, reloaded on SIGHUP
+    #[structopt(long, requires = "tls-certificate")]
+    tls_key: Option<PathBuf>,
+
+    /// Where to serve the Prometheus metrics, on `/metrics`
+    #[structopt(long)]
+    metrics_address: Option<SocketAddr>,
+
+    /// Snapshot to load the directory from at boot
+    #[structopt(long)]
+    load_snapshot: Option<PathBuf>,
+    /// Where to dump the directory on SIGTERM or SIGINT
+    #[structopt(long)]
+    dump_snapshot: Option<PathBuf>,
+    /// Also dump the directory every given seconds
+    #[structopt(long, requires = "dump-snapshot")]
+    dump_interval: Option<u64>,
+
+    /// Seconds after which an account not put again is dropped, freeing its name
+    #[structopt(long)]
+    ttl: Option<u64>,
+    /// Seconds between two checks for expired accounts
+    #[structopt(long, default_value = "60")]
+    sweep_interval: u64,
+
+    /// JSON list of the public keys allowed to mark accounts as official
+    #[structopt(long)]
+    admins: Option<PathBuf>,
+
+    /// Other instance to replicate the directory from, can be repeated
+    #[structopt(long = "peer")]
+    peers: Vec<Uri>,
+    /// Seconds between two replications
+    #[structopt(long, default_value = "5")]
+    replication_interval: u64,
+
+    /// Share the claims with other instances via sieve, configured by the given JSON file
+    #[cfg(feature = "ledger")]
+    #[structopt(long)]
+    ledger_config: Option<PathBuf>,
 }
 
 #[derive(Debug, snafu::Snafu)]
 enum Error {
     #[snafu(display("logging: {}", source))]
     Logging {
-        source: tracing::dispatcher::SetGlobalDefaultError,
+        source: Box<dyn std::error::Error + Send + Sync>,
+    },
+    #[snafu(display("log filter: {}", source))]
+    LogFilter {
+        source: tracing_subscriber::filter::ParseError,
     },
     #[snafu(display("service: {}", source))]
     Service { source: tonic::transport::Error },
     #[snafu(display("rpc: {}", source))]
     Rpc { source: tonic::transport::Error },
+    #[snafu(display("bind: {}", source))]
+    Bind { source: io::Error },
+    #[snafu(display("tls: {}", source))]
+    Tls { source: tls::Error },
+    #[snafu(display("snapshot: {}", source))]
+    Snapshot { source: snapshot::Error },
+    #[snafu(display("restore: {}", source))]
+    Restore { source: accounts::Error },
+    #[snafu(display("close: {}", source))]
+    Close { source: accounts::Error },
+    #[snafu(display("signal: {}", source))]
+    Signal { source: io::Error },
+    #[snafu(display("read admins: {}", source))]
+    ReadAdmins { source: io::Error },
+    #[snafu(display("parse admins: {}", source))]
+    ParseAdmins { source: serde_json::Error },
+    #[snafu(display("metrics: {}", source))]
+    Metrics { source: prometheus::Error },
+    #[snafu(display("ledger: {}", source))]
+    Ledger {
+        source: Box<dyn std::error::Error + Send + Sync>,
+    },
 }
 
-async fn run(address: SocketAddr) -> Result<(), Error> {
-    let subscriber = FmtSubscriber::builder()
-        .with_max_level(Level::TRACE)
-        .finish();
-    subscriber::set_global_default(subscriber).context(Logging)?;
+async fn run(arguments: Arguments) -> Result<(), Error> {
+    let logs = tracing_subscriber::fmt()
+        .with_env_filter(EnvFilter::try_new(&arguments.log).context(LogFilter)?);
+    match arguments.log_format {
+        LogFormat::Text => logs.try_init(),
+        LogFormat::Json => logs.json().try_init(),
+    }
+    .context(Logging)?;
+
+    let tls = match (&arguments.tls_certificate, &arguments.tls_key) {
+        (Some(certificate), Some(key)) => {
+            Some(tls::Tls::load(certificate.clone(), key.clone()).context(Tls)?)
+        }
+        _ => None,
+    };
+    if let Some(tls) = tls.clone() {
+        let mut hangup = signal(SignalKind::hangup()).context(Signal)?;
+
+        tokio::spawn(async move {
+            while hangup.recv().await.is_some() {
+                match tls.reload() {
+                    Ok(()) => tracing::info!("reloaded TLS certificate"),
+                    Err(err) => tracing::error!("unable to reload TLS certificate: {}", err),
+                }
+            }
+        });
+    }
+
+    let ttl = arguments.ttl.map(Duration::from_secs);
+    let accounts = match &arguments.load_snapshot {
+        Some(path) => {
+            accounts::Accounts::restore(snapshot::load(path).await.context(Snapshot)?, ttl)
+                .context(Restore)?
+        }
+        None => accounts::Accounts::with_ttl(ttl),
+    };
 
-    let service = rpc::Service::new();
+    if ttl.is_some() {
+        let accounts = accounts.clone();
+        let every = Duration::from_secs(arguments.sweep_interval);
 
-    let config = tonic_web::config().allow_all_origins();
+        tokio::spawn(async move {
+            let mut interval = time::interval(every);
 
-    Server::builder()
+            loop {
+                interval.tick().await;
+
+                match accounts.sweep().await {
+                    Ok(expired) if !expired.is_empty() => {
+                        tracing::info!("{} accounts expired", expired.len())
+                    }
+                    Ok(_) => {}
+                    Err(err) => tracing::error!("unable to sweep accounts: {}", err),
+                }
+            }
+        });
+    }
+
+    // stopped between dumps, to not write the same file as the final one
+    let mut dumper = None;
+    if let (Some(path), Some(interval)) = (arguments.dump_snapshot.clone(), arguments.dump_interval)
+    {
+        let accounts = accounts.clone();
+        let (stop, mut stopped) = oneshot::channel::<()>();
+
+        let handle = tokio::spawn(async move {
+            let mut interval = time::interval(Duration::from_secs(interval));
+
+            loop {
+                tokio::select! {
+                    _ = interval.tick() => {}
+                    _ = &mut stopped => break,
+                }
+
+                if let Err(err) = snapshot::dump(&path, &accounts).await {
+                    tracing::error!("unable to dump snapshot: {}", err);
+                }
+            }
+        });
+        dumper = Some((stop, handle));
+    }
+
+    if !arguments.peers.is_empty() {
+        replication::spawn(
+            accounts.clone(),
+            arguments.peers.clone(),
+            Duration::from_secs(arguments.replication_interval),
+        );
+    }
+
+    let admins: HashSet<sign::PublicKey> = match &arguments.admins {
+        Some(path) => {
+            serde_json::from_slice(&fs::read(path).context(ReadAdmins)?).context(ParseAdmins)?
+        }
+        None => HashSet::new(),
+    };
+    let metrics = Arc::new(metrics::Metrics::new().context(Metrics)?);
+    if let Some(address) = arguments.metrics_address {
+        let (metrics, accounts) = (metrics.clone(), accounts.clone());
+
+        tokio::spawn(async move {
+            if let Err(err) = metrics::serve(metrics, accounts, address).await {
+                tracing::error!("unable to serve metrics: {}", err);
+            }
+        });
+    }
+
+    let service = rpc::Service::new(accounts.clone())
+        .with_admins(admins)
+        .with_metrics(metrics);
+
+    #[cfg(feature = "ledger")]
+    let service = match &arguments.ledger_config {
+        Some(path) => {
+            let config = ledger::sieve::Config::load(path).context(Ledger)?;
+            let broadcast = ledger::sieve::start(config).await.context(Ledger)?;
+
+            service.with_ledger(ledger::Ledger::start(accounts.clone(), Arc::new(broadcast)))
+        }
+        None => service,
+    };
+
+    // cross-origin calls are only allowed from the given websites
+    let config = if arguments.allowed_origins.iter().any(|origin| origin == "*") {
+        tonic_web::config().allow_all_origins()
+    } else {
+        tonic_web::config().allow_origins(arguments.allowed_origins.clone())
+    };
+
+    // ready once everything is loaded
+    let (mut health_reporter, health_service) = tonic_health::server::health_reporter();
+    health_reporter
+        .set_serving::<NameServiceServer<rpc::Service>>()
+        .await;
+
+    let mut terminate = signal(SignalKind::terminate()).context(Signal)?;
+    let mut interrupt = signal(SignalKind::interrupt()).context(Signal)?;
+    let shutdown = async move {
+        tokio::select! {
+            _ = terminate.recv() => {}
+            _ = interrupt.recv() => {}
+        }
+        tracing::info!("terminating");
+
+        health_reporter
+            .set_not_serving::<NameServiceServer<rpc::Service>>()
+            .await;
+    };
+
+    // returns once the in-flight requests are answered
+    let router = Server::builder()
         .accept_http1(true)
         .add_service(config.enable(NameServiceServer::new(service)))
-        .serve(address)
-        .await
-        .context(Rpc)?;
+        .add_service(health_service);
+    match tls {
+        Some(tls) => {
+            let listener = TcpListener::bind(arguments.address).await.context(Bind)?;
+            router
+                .serve_with_incoming_shutdown(tls.incoming(listener), shutdown)
+                .await
+                .context(Rpc)?
+        }
+        None => router
+            .serve_with_shutdown(arguments.address, shutdown)
+            .await
+            .context(Rpc)?,
+    }
+
+    if let Some((stop, handle)) = dumper {
+        drop(stop);
+        if let Err(err) = handle.await {
+            tracing::error!("periodic dump failed: {}", err);
+        }
+    }
+
+    // background tasks might still be sending commands
+    let accounts = accounts.close().await.context(Close)?;
+    if let Some(path) = &arguments.dump_snapshot {
+        snapshot::write(path, accounts).await.context(Snapshot)?;
+    }
 
     Ok(())
 }
@@ -51,7 +338,7 @@ async fn run(address: SocketAddr) -> Result<(), Error> {
 async fn main() {
     let arguments = Arguments::from_args();
 
-    run(arguments.address).await.unwrap_or_else(|err| {
+    run(arguments).await.unwrap_or_else(|err| {
         eprintln!("error running cmd: {}", err);
         process::exit(1);
     });
@@ -0,0 +1,150 @@
+//! Prometheus metrics, served over HTTP
+
+use std::{convert::Infallible, net::SocketAddr, sync::Arc};
+
+use hyper::{
+    header::CONTENT_TYPE,
+    service::{make_service_fn, service_fn},
+    Body, Request, Response, Server, StatusCode,
+};
+use prometheus::{
+    histogram_opts, opts, Encoder, HistogramTimer, HistogramVec, IntCounterVec, IntGauge, Registry,
+    TextEncoder,
+};
+
+use super::accounts::Accounts;
+
+pub struct Metrics {
+    registry: Registry,
+    requests: IntCounterVec,
+    latencies: HistogramVec,
+    accounts: IntGauge,
+    rejected_puts: IntCounterVec,
+}
+
+impl Metrics {
+    pub fn new() -> Result<Self, prometheus::Error> {
+        let registry = Registry::new_custom(Some("at2_ns".to_owned()), None)?;
+
+        let requests = IntCounterVec::new(
+            opts!("requests_total", "Requests received, by RPC"),
+            &["rpc"],
+        )?;
+        let latencies = HistogramVec::new(
+            histogram_opts!("request_duration_seconds", "Time to answer, by RPC"),
+            &["rpc"],
+        )?;
+        let accounts = IntGauge::new("accounts", "Registered accounts")?;
+        let rejected_puts = IntCounterVec::new(
+            opts!("rejected_puts_total", "Puts refused, by reason"),
+            &["reason"],
+        )?;
+
+        registry.register(Box::new(requests.clone()))?;
+        registry.register(Box::new(latencies.clone()))?;
+        registry.register(Box::new(accounts.clone()))?;
+        registry.register(Box::new(rejected_puts.clone()))?;
+
+        Ok(Self {
+            registry,
+            requests,
+            latencies,
+            accounts,
+            rejected_puts,
+        })
+    }
+
+    /// Count a request, its latency being recorded when the timer is dropped
+    pub fn request(&self, rpc: &str) -> HistogramTimer {
+        self.requests.with_label_values(&[rpc]).inc();
+        self.latencies.with_label_values(&[rpc]).start_timer()
+    }
+
+    pub fn rejected_put(&self, reason: &str) {
+        self.rejected_puts.with_label_values(&[reason]).inc();
+    }
+
+    async fn respond(&self, request: Request<Body>, accounts: &Accounts) -> Response<Body> {
+        if request.uri().path() != "/metrics" {
+            return Response::builder()
+                .status(StatusCode::NOT_FOUND)
+                .body(Body::empty())
+                .expect("valid response");
+        }
+
+        // only counted when scraped, to avoid tracking every change
+        match accounts.get_all().await {
+            Ok(all) => self.accounts.set(all.len() as i64),
+            Err(err) => tracing::warn!("unable to count accounts: {}", err),
+        }
+
+        let encoder = TextEncoder::new();
+        let mut buffer = Vec::new();
+        if let Err(err) = encoder.encode(&self.registry.gather(), &mut buffer) {
+            tracing::error!("unable to encode metrics: {}", err);
+
+            return Response::builder()
+                .status(StatusCode::INTERNAL_SERVER_ERROR)
+                .body(Body::empty())
+                .expect("valid response");
+        }
+
+        Response::builder()
+            .header(CONTENT_TYPE, encoder.format_type())
+            .body(Body::from(buffer))
+            .expect("valid response")
+    }
+}
+
+/// Serve the metrics on `/metrics`
+pub async fn serve(
+    metrics: Arc<Metrics>,
+    accounts: Accounts,
+    address: SocketAddr,
+) -> Result<(), hyper::Error> {
+    let make_service = make_service_fn(move |_| {
+        let (metrics, accounts) = (metrics.clone(), accounts.clone());
+
+        async move {
+            Ok::<_, Infallible>(service_fn(move |request| {
+                let (metrics, accounts) = (metrics.clone(), accounts.clone());
+
+                async move { Ok::<_, Infallible>(metrics.respond(request, &accounts).await) }
+            }))
+        }
+    });
+
+    Server::bind(&address).serve(make_service).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Metrics;
+
+    #[test]
+    fn requests_are_counted_by_rpc() {
+        let metrics = Metrics::new().expect("create metrics");
+
+        drop(metrics.request("put"));
+        drop(metrics.request("put"));
+        drop(metrics.request("get_all"));
+        metrics.rejected_put("name_taken");
+
+        assert_eq!(metrics.requests.with_label_values(&["put"]).get(), 2);
+        assert_eq!(metrics.requests.with_label_values(&["get_all"]).get(), 1);
+        assert_eq!(
+            metrics
+                .latencies
+                .with_label_values(&["put"])
+                .get_sample_count(),
+            2
+        );
+        assert_eq!(
+            metrics
+                .rejected_puts
+                .with_label_values(&["name_taken"])
+                .get(),
+            1
+        );
+    }
+}
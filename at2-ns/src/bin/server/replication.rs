@@ -0,0 +1,365 @@
+//! Replicate the directory between instances
+//!
+//! Each instance regularly pulls the records of its peers and merges them in
+//! its own, see [`Accounts::merge`] for how conflicts are resolved.
+
+use std::time::Duration;
+
+use at2_ns::{
+    from_timestamp,
+    proto::{self, name_service_client::NameServiceClient},
+    to_timestamp, History,
+};
+use drop::crypto::sign;
+use http::Uri;
+use snafu::{OptionExt, ResultExt};
+use tokio::{task::JoinHandle, time};
+use tonic::transport::{Channel, Endpoint};
+
+use super::accounts::{self, Account, Accounts, Verification};
+
+#[derive(snafu::Snafu, Debug)]
+pub enum Error {
+    #[snafu(display("missing required field"))]
+    MissingRequiredField,
+    #[snafu(display("invalid serialization: {}", source))]
+    InvalidSerialization { source: bincode::Error },
+    #[snafu(display("peer answered: {}", source.message()))]
+    Rpc { source: tonic::Status },
+    #[snafu(display("directory: {}", source))]
+    Directory { source: accounts::Error },
+}
+
+/// Convert an account, with its history, to send it to another instance
+pub fn encode(
+    public_key: sign::PublicKey,
+    account: Account,
+    history: History,
+) -> Result<proto::Record, bincode::Error> {
+    Ok(proto::Record {
+        account: Some(proto::Account {
+            public_key: bincode::serialize(&public_key)?,
+            verified: account.is_verified(),
+            name: account.name,
+            profile: Some(account.profile.into()),
+        }),
+        registered_at: to_timestamp(account.registered_at),
+        updated_at: to_timestamp(account.updated_at),
+        renewed_at: to_timestamp(account.renewed_at),
+        attested_at: account
+            .verification
+            .map_or(0, |verification| to_timestamp(verification.issued_at)),
+        history: history.changes().iter().cloned().map(Into::into).collect(),
+    })
+}
+
+/// Convert back an account received from another instance
+pub fn decode(record: proto::Record) -> Result<(sign::PublicKey, Account, History), Error> {
+    let account = record.account.context(MissingRequiredField)?;
+    let verification = (record.attested_at != 0).then(|| Verification {
+        verified: account.verified,
+        issued_at: from_timestamp(record.attested_at),
+    });
+
+    Ok((
+        bincode::deserialize(&account.public_key).context(InvalidSerialization)?,
+        Account {
+            name: account.name,
+            profile: account.profile.map(Into::into).unwrap_or_default(),
+            registered_at: from_timestamp(record.registered_at),
+            updated_at: from_timestamp(record.updated_at),
+            renewed_at: from_timestamp(record.renewed_at.max(record.updated_at)),
+            verification,
+        },
+        record.history.into_iter().map(Into::into).collect(),
+    ))
+}
+
+async fn pull(accounts: &Accounts, peer: &mut NameServiceClient<Channel>) -> Result<(), Error> {
+    let records = peer
+        .get_records(proto::GetRecordsRequest {})
+        .await
+        .context(Rpc)?
+        .into_inner()
+        .records
+        .into_iter()
+        .map(decode)
+        .collect::<Result<_, _>>()?;
+
+    accounts.merge(records).await.context(Directory)
+}
+
+/// Regularly merge the records of the given peers
+pub fn spawn(accounts: Accounts, peers: Vec<Uri>, every: Duration) -> JoinHandle<()> {
+    let mut peers = peers
+        .into_iter()
+        .map(|uri| {
+            (
+                uri.clone(),
+                NameServiceClient::new(Endpoint::from(uri).connect_lazy()),
+            )
+        })
+        .collect::<Vec<_>>();
+
+    tokio::spawn(async move {
+        let mut interval = time::interval(every);
+
+        loop {
+            interval.tick().await;
+
+            for (uri, peer) in peers.iter_mut() {
+                if let Err(err) = pull(&accounts, peer).await {
+                    tracing::warn!("unable to replicate from {}: {}", uri, err);
+                }
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::HashMap,
+        time::{Duration, SystemTime},
+    };
+
+    use at2_ns::{proto::name_service_server::NameServiceServer, Profile};
+    use drop::crypto::sign::{self, KeyPair};
+    use http::Uri;
+    use tokio::{net::TcpListener, time};
+    use tokio_stream::wrappers::TcpListenerStream;
+    use tonic::transport::Server;
+
+    use super::spawn;
+    use crate::{
+        accounts::{Account, Accounts},
+        rpc::Service,
+    };
+
+    const INTERVAL: Duration = Duration::from_millis(20);
+
+    /// Serve every accounts, each replicating from all the others
+    async fn start_instances(instances: Vec<Accounts>) -> Vec<Accounts> {
+        let mut listeners = Vec::with_capacity(instances.len());
+        for _ in 0..instances.len() {
+            listeners.push(TcpListener::bind("127.0.0.1:0").await.expect("bind"));
+        }
+        let uris = listeners
+            .iter()
+            .map(|listener| {
+                format!("http://{}", listener.local_addr().expect("local address"))
+                    .parse::<Uri>()
+                    .expect("valid uri")
+            })
+            .collect::<Vec<_>>();
+
+        for (index, (accounts, listener)) in instances.iter().zip(listeners).enumerate() {
+            tokio::spawn(
+                Server::builder()
+                    .add_service(NameServiceServer::new(Service::new(accounts.clone())))
+                    .serve_with_incoming(TcpListenerStream::new(listener)),
+            );
+
+            let peers = uris
+                .iter()
+                .enumerate()
+                .filter(|(peer, _)| *peer != index)
+                .map(|(_, uri)| uri.clone())
+                .collect();
+            spawn(accounts.clone(), peers, INTERVAL);
+        }
+
+        instances
+    }
+
+    async fn eventually_all_equal(
+        instances: &[Accounts],
+        expected: HashMap<sign::PublicKey, String>,
+    ) {
+        for _ in 0..100 {
+            let mut converged = true;
+            for accounts in instances {
+                let names = accounts
+                    .get_all()
+                    .await
+                    .expect("get all")
+                    .into_iter()
+                    .map(|contact| (*contact.public_key(), contact.name))
+                    .collect::<HashMap<_, _>>();
+                converged &= names == expected;
+            }
+
+            if converged {
+                return;
+            }
+
+            time::sleep(INTERVAL).await;
+        }
+
+        panic!("instances never converged to {:?}", expected);
+    }
+
+    fn account(name: &str, updated_at: SystemTime) -> Account {
+        Account {
+            name: name.to_owned(),
+            profile: Profile::default(),
+            registered_at: updated_at,
+            updated_at,
+            renewed_at: updated_at,
+            verification: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn put_is_replicated() {
+        let instances =
+            start_instances(vec![Accounts::new(), Accounts::new(), Accounts::new()]).await;
+        let pubkey = KeyPair::random().public();
+
+        instances[0]
+            .put(
+                pubkey,
+                "user".to_owned(),
+                Profile::default(),
+                SystemTime::now(),
+            )
+            .await
+            .expect("put user");
+
+        eventually_all_equal(
+            &instances,
+            vec![(pubkey, "user".to_owned())].into_iter().collect(),
+        )
+        .await;
+    }
+
+    #[tokio::test]
+    async fn rename_on_another_instance_is_replicated() {
+        let instances = start_instances(vec![Accounts::new(), Accounts::new()]).await;
+        let pubkey = KeyPair::random().public();
+
+        instances[0]
+            .put(
+                pubkey,
+                "first".to_owned(),
+                Profile::default(),
+                SystemTime::now(),
+            )
+            .await
+            .expect("first put");
+        eventually_all_equal(
+            &instances,
+            vec![(pubkey, "first".to_owned())].into_iter().collect(),
+        )
+        .await;
+
+        instances[1]
+            .put(
+                pubkey,
+                "second".to_owned(),
+                Profile::default(),
+                SystemTime::now(),
+            )
+            .await
+            .expect("second put");
+        eventually_all_equal(
+            &instances,
+            vec![(pubkey, "second".to_owned())].into_iter().collect(),
+        )
+        .await;
+    }
+
+    #[tokio::test]
+    async fn concurrent_claims_keep_the_earliest() {
+        let now = SystemTime::now();
+        let (first, second) = (KeyPair::random().public(), KeyPair::random().public());
+
+        let instances = start_instances(vec![
+            Accounts::restore(
+                vec![(first, account("user", now))].into_iter().collect(),
+                None,
+            )
+            .expect("restore first"),
+            Accounts::restore(
+                vec![(second, account("user", now + Duration::from_secs(1)))]
+                    .into_iter()
+                    .collect(),
+                None,
+            )
+            .expect("restore second"),
+        ])
+        .await;
+
+        eventually_all_equal(
+            &instances,
+            vec![(first, "user".to_owned())].into_iter().collect(),
+        )
+        .await;
+    }
+
+    #[tokio::test]
+    async fn older_names_are_replicated() {
+        let instances = start_instances(vec![Accounts::new(), Accounts::new()]).await;
+        let pubkey = KeyPair::random().public();
+        let now = SystemTime::now();
+
+        for (name, signed_at) in [("first", now), ("second", now + Duration::from_secs(1))] {
+            instances[0]
+                .put(pubkey, name.to_owned(), Profile::default(), signed_at)
+                .await
+                .expect("put user");
+        }
+        eventually_all_equal(
+            &instances,
+            vec![(pubkey, "second".to_owned())].into_iter().collect(),
+        )
+        .await;
+
+        assert_eq!(
+            instances[1].history(pubkey).await.expect("history"),
+            instances[0].history(pubkey).await.expect("history"),
+        );
+    }
+
+    #[tokio::test]
+    async fn profile_update_does_not_make_a_claim_more_recent() {
+        let now = SystemTime::now();
+        let (first, second) = (KeyPair::random().public(), KeyPair::random().public());
+
+        let (with_first, with_second) = (Accounts::new(), Accounts::new());
+        with_first
+            .put(first, "user".to_owned(), Profile::default(), now)
+            .await
+            .expect("put first");
+        with_second
+            .put(
+                second,
+                "user".to_owned(),
+                Profile::default(),
+                now + Duration::from_secs(3),
+            )
+            .await
+            .expect("put second");
+        let profile = Profile {
+            bio: Some("hello".to_owned()),
+            ..Profile::default()
+        };
+        with_first
+            .put(
+                first,
+                "user".to_owned(),
+                profile,
+                now + Duration::from_secs(5),
+            )
+            .await
+            .expect("update first");
+
+        let instances = start_instances(vec![with_first, with_second]).await;
+
+        eventually_all_equal(
+            &instances,
+            vec![(first, "user".to_owned())].into_iter().collect(),
+        )
+        .await;
+    }
+}
@@ -1,8 +1,19 @@
-use at2_ns::proto;
+use std::{collections::HashSet, sync::Arc, time::SystemTime};
+
+use at2_ns::{proto, Attestation, Profile};
 use drop::crypto::sign;
-use snafu::{OptionExt, ResultExt};
+use snafu::{ensure, OptionExt, ResultExt};
+
+#[cfg(feature = "ledger")]
+use super::ledger::{Claim, Ledger};
+use super::{
+    accounts::{self, Accounts, Verification},
+    metrics::Metrics,
+    replication,
+};
 
-use super::accounts::{self, Accounts};
+/// Most puts in a batch, as atomic ones copy the whole directory
+pub const MAX_BATCH: usize = 1_000;
 
 #[derive(snafu::Snafu, Debug)]
 pub enum ProtoError {
@@ -12,70 +23,734 @@ pub enum ProtoError {
     InvalidSerialization { source: bincode::Error },
     #[snafu(display("invalid signature: {}", source))]
     InvalidSignature { source: sign::VerifyError },
+    #[snafu(display("invalid profile: fields too long or url not a web link"))]
+    InvalidProfile,
+    #[snafu(display("claim not signed around now, check the clock"))]
+    NotRecent,
+    #[snafu(display("not signed by an admin"))]
+    UnknownAdmin,
+    #[snafu(display("batch of more than {} puts", MAX_BATCH))]
+    BatchTooLarge,
+}
+
+/// Put checked to be signed by its public key
+struct Put {
+    pubkey: sign::PublicKey,
+    name: String,
+    profile: Profile,
+    signed_at: SystemTime,
+    #[cfg_attr(not(feature = "ledger"), allow(dead_code))] // only shared via the ledger
+    signature: sign::Signature,
 }
 
 pub struct Service {
     accounts: Accounts,
+    admins: HashSet<sign::PublicKey>,
+    metrics: Arc<Metrics>,
+    #[cfg(feature = "ledger")]
+    ledger: Option<Ledger>,
 }
 
 impl Service {
-    pub fn new() -> Self {
+    pub fn new(accounts: Accounts) -> Self {
         Self {
-            accounts: Accounts::new(),
+            accounts,
+            admins: HashSet::new(),
+            metrics: Arc::new(Metrics::new().expect("metrics are valid")),
+            #[cfg(feature = "ledger")]
+            ledger: None,
+        }
+    }
+
+    /// Accept attestations signed by these public keys
+    pub fn with_admins(mut self, admins: HashSet<sign::PublicKey>) -> Self {
+        self.admins = admins;
+        self
+    }
+
+    /// Record the requests in the given metrics
+    pub fn with_metrics(mut self, metrics: Arc<Metrics>) -> Self {
+        self.metrics = metrics;
+        self
+    }
+
+    fn rejected_put(&self, reason: &str, err: impl Into<tonic::Status>) -> tonic::Status {
+        self.metrics.rejected_put(reason);
+        err.into()
+    }
+
+    /// Also share the accepted claims via the given ledger
+    #[cfg(feature = "ledger")]
+    pub fn with_ledger(mut self, ledger: Ledger) -> Self {
+        self.ledger = Some(ledger);
+        self
+    }
+
+    #[cfg(feature = "ledger")]
+    async fn share(&self, put: Put) {
+        if let Some(ledger) = &self.ledger {
+            let claim = Claim {
+                public_key: put.pubkey,
+                name: put.name,
+                profile: put.profile,
+                signature: put.signature,
+                signed_at: put.signed_at,
+            };
+
+            // already accepted locally, so don't fail the request
+            if let Err(err) = ledger.share(claim).await {
+                tracing::warn!("unable to share claim: {}", err);
+            }
+        }
+    }
+
+    #[cfg(not(feature = "ledger"))]
+    async fn share(&self, _: Put) {}
+}
+
+impl ProtoError {
+    /// Label in the metrics
+    fn reason(&self) -> &'static str {
+        match self {
+            ProtoError::MissingRequiredField | ProtoError::InvalidSerialization { .. } => {
+                "malformed"
+            }
+            ProtoError::InvalidSignature { .. } => "invalid_signature",
+            ProtoError::InvalidProfile => "invalid_profile",
+            ProtoError::NotRecent => "not_recent",
+            ProtoError::UnknownAdmin => "unknown_admin",
+            ProtoError::BatchTooLarge => "batch_too_large",
+        }
+    }
+}
+
+impl accounts::Error {
+    /// Label in the metrics
+    fn reason(&self) -> &'static str {
+        match self {
+            accounts::Error::AlreadyExisting => "name_taken",
+            accounts::Error::NotFound => "not_found",
+            accounts::Error::Outdated => "outdated",
+            accounts::Error::Aborted => "aborted",
+            accounts::Error::GoneOnSend | accounts::Error::GoneOnRecv => "internal",
         }
     }
 }
 
 impl From<ProtoError> for tonic::Status {
     fn from(err: ProtoError) -> Self {
-        Self::invalid_argument(err.to_string())
+        match err {
+            ProtoError::UnknownAdmin => Self::permission_denied(err.to_string()),
+            ProtoError::MissingRequiredField
+            | ProtoError::InvalidSerialization { .. }
+            | ProtoError::InvalidSignature { .. }
+            | ProtoError::InvalidProfile
+            | ProtoError::NotRecent
+            | ProtoError::BatchTooLarge => Self::invalid_argument(err.to_string()),
+        }
     }
 }
 impl From<accounts::Error> for tonic::Status {
     fn from(err: accounts::Error) -> Self {
-        Self::invalid_argument(err.to_string())
+        match err {
+            accounts::Error::AlreadyExisting => Self::already_exists(err.to_string()),
+            accounts::Error::NotFound => Self::not_found(err.to_string()),
+            accounts::Error::Outdated => Self::failed_precondition(err.to_string()),
+            accounts::Error::Aborted => Self::aborted(err.to_string()),
+            // the directory stopped, as when shutting down
+            accounts::Error::GoneOnSend => Self::unavailable(err.to_string()),
+            // the directory failed while handling the request
+            accounts::Error::GoneOnRecv => Self::internal(err.to_string()),
+        }
     }
 }
 
+/// Check that a put is well formed and recently signed by its public key
+fn parse_put(message: proto::PutRequest) -> Result<Put, ProtoError> {
+    let account = message.account.context(MissingRequiredField)?;
+
+    let pubkey: sign::PublicKey =
+        bincode::deserialize(&account.public_key).context(InvalidSerialization)?;
+    let signature: sign::Signature =
+        bincode::deserialize(&message.signature).context(InvalidSerialization)?;
+
+    let profile: Profile = account.profile.map(Into::into).unwrap_or_default();
+    ensure!(profile.is_valid(), InvalidProfile);
+
+    let signed_at = at2_ns::from_timestamp(message.signed_at);
+    ensure!(accounts::is_recent(signed_at, SystemTime::now()), NotRecent);
+
+    profile
+        .verify(&signature, &pubkey, &account.name, signed_at)
+        .context(InvalidSignature)?;
+
+    Ok(Put {
+        pubkey,
+        name: account.name,
+        profile,
+        signed_at,
+        signature,
+    })
+}
+
 #[tonic::async_trait]
 impl proto::name_service_server::NameService for Service {
     async fn put(
         &self,
         request: tonic::Request<proto::PutRequest>,
     ) -> Result<tonic::Response<proto::PutReply>, tonic::Status> {
-        let message = request.into_inner();
-        let account = message.account.context(MissingRequiredField)?;
-
-        let pubkey: sign::PublicKey =
-            bincode::deserialize(&account.public_key).context(InvalidSerialization)?;
-        let signature: sign::Signature =
-            bincode::deserialize(&message.signature).context(InvalidSerialization)?;
+        let _timer = self.metrics.request("put");
 
-        signature
-            .verify(&account.name, &pubkey)
-            .context(InvalidSignature)?;
+        let put =
+            parse_put(request.into_inner()).map_err(|err| self.rejected_put(err.reason(), err))?;
 
-        self.accounts.put(pubkey, account.name).await?;
+        self.accounts
+            .put(
+                put.pubkey,
+                put.name.clone(),
+                put.profile.clone(),
+                put.signed_at,
+            )
+            .await
+            .map_err(|err| self.rejected_put(err.reason(), err))?;
+        self.share(put).await;
 
         Ok(tonic::Response::new(proto::PutReply {}))
     }
 
+    async fn put_batch(
+        &self,
+        request: tonic::Request<proto::PutBatchRequest>,
+    ) -> Result<tonic::Response<proto::PutBatchReply>, tonic::Status> {
+        let _timer = self.metrics.request("put_batch");
+
+        let request = request.into_inner();
+        ensure!(request.puts.len() <= MAX_BATCH, BatchTooLarge);
+
+        let parsed = request.puts.into_iter().map(parse_put).collect::<Vec<_>>();
+        let malformed = parsed.iter().any(Result::is_err);
+
+        let puts = if request.atomic && malformed {
+            Vec::new()
+        } else {
+            parsed
+                .iter()
+                .filter_map(|put| put.as_ref().ok())
+                .map(|put| {
+                    (
+                        put.pubkey,
+                        put.name.clone(),
+                        put.profile.clone(),
+                        put.signed_at,
+                    )
+                })
+                .collect()
+        };
+        let mut stored = self
+            .accounts
+            .put_batch(puts, request.atomic)
+            .await?
+            .into_iter();
+
+        let mut accepted = Vec::new();
+        let statuses = parsed
+            .into_iter()
+            .map(|put| match put {
+                Err(err) => self.rejected_put(err.reason(), err),
+                Ok(_) if request.atomic && malformed => {
+                    let err = accounts::Error::Aborted;
+                    self.rejected_put(err.reason(), err)
+                }
+                Ok(put) => match stored.next() {
+                    Some(Ok(_)) => {
+                        accepted.push(put);
+                        tonic::Status::new(tonic::Code::Ok, "")
+                    }
+                    Some(Err(err)) => self.rejected_put(err.reason(), err),
+                    None => tonic::Status::internal("missing result of put"),
+                },
+            })
+            .map(|status| proto::PutStatus {
+                code: status.code() as i32,
+                message: status.message().to_owned(),
+            })
+            .collect();
+
+        for put in accepted {
+            self.share(put).await;
+        }
+
+        Ok(tonic::Response::new(proto::PutBatchReply { statuses }))
+    }
+
     async fn get_all(
         &self,
         _: tonic::Request<proto::GetAllRequest>,
     ) -> Result<tonic::Response<proto::GetAllReply>, tonic::Status> {
+        let _timer = self.metrics.request("get_all");
+
         let accounts = self
             .accounts
             .get_all()
             .await?
             .drain()
-            .map(|(public_key, name)| {
-                bincode::serialize(&public_key)
+            .map(|contact| {
+                bincode::serialize(contact.public_key())
                     .context(InvalidSerialization)
-                    .map(|public_key| proto::Account { public_key, name })
+                    .map(|public_key| proto::Account {
+                        public_key,
+                        name: contact.name,
+                        profile: Some(contact.profile.into()),
+                        verified: contact.verified,
+                    })
             })
             .collect::<Result<_, _>>()?;
 
         Ok(tonic::Response::new(proto::GetAllReply { accounts }))
     }
+
+    async fn get_records(
+        &self,
+        _: tonic::Request<proto::GetRecordsRequest>,
+    ) -> Result<tonic::Response<proto::GetRecordsReply>, tonic::Status> {
+        let _timer = self.metrics.request("get_records");
+
+        let mut state = self.accounts.snapshot().await?;
+        let records = state
+            .accounts
+            .drain()
+            .map(|(public_key, account)| {
+                let history = state.history.remove(&public_key).unwrap_or_default();
+                replication::encode(public_key, account, history).context(InvalidSerialization)
+            })
+            .collect::<Result<_, _>>()?;
+
+        Ok(tonic::Response::new(proto::GetRecordsReply { records }))
+    }
+
+    async fn get_history(
+        &self,
+        request: tonic::Request<proto::GetHistoryRequest>,
+    ) -> Result<tonic::Response<proto::GetHistoryReply>, tonic::Status> {
+        let _timer = self.metrics.request("get_history");
+
+        let pubkey: sign::PublicKey =
+            bincode::deserialize(&request.into_inner().public_key).context(InvalidSerialization)?;
+
+        let changes = self
+            .accounts
+            .history(pubkey)
+            .await?
+            .changes()
+            .iter()
+            .cloned()
+            .map(Into::into)
+            .collect();
+
+        Ok(tonic::Response::new(proto::GetHistoryReply { changes }))
+    }
+
+    async fn attest(
+        &self,
+        request: tonic::Request<proto::AttestRequest>,
+    ) -> Result<tonic::Response<proto::AttestReply>, tonic::Status> {
+        let _timer = self.metrics.request("attest");
+
+        let attestation: Attestation = request
+            .into_inner()
+            .attestation
+            .context(MissingRequiredField)?
+            .try_into()
+            .context(InvalidSerialization)?;
+
+        ensure!(self.admins.contains(&attestation.admin), UnknownAdmin);
+        attestation.verify().context(InvalidSignature)?;
+
+        self.accounts
+            .attest(
+                attestation.subject,
+                Verification {
+                    verified: attestation.verified,
+                    issued_at: attestation.issued_at,
+                },
+            )
+            .await?;
+
+        Ok(tonic::Response::new(proto::AttestReply {}))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::HashSet,
+        time::{Duration, SystemTime},
+    };
+
+    use at2_ns::{
+        client::{self, NativeClient},
+        proto::{
+            self, name_service_client::NameServiceClient, name_service_server::NameServiceServer,
+        },
+        Attestation, Contact, Profile, User,
+    };
+    use drop::crypto::sign::KeyPair;
+    use http::Uri;
+    use tokio::net::TcpListener;
+    use tokio_stream::wrappers::TcpListenerStream;
+    use tonic::{
+        transport::{Channel, Endpoint, Server},
+        Code,
+    };
+
+    use super::{Service, MAX_BATCH};
+    use crate::accounts::Accounts;
+
+    /// Serve on an ephemeral port, returning a client and a raw one to send malformed requests
+    async fn start(service: Service) -> (NativeClient, NameServiceClient<Channel>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.expect("bind");
+        let uri = format!("http://{}", listener.local_addr().expect("local address"))
+            .parse::<Uri>()
+            .expect("valid uri");
+
+        tokio::spawn(
+            Server::builder()
+                .add_service(NameServiceServer::new(service))
+                .serve_with_incoming(TcpListenerStream::new(listener)),
+        );
+
+        (
+            NativeClient::new(&uri),
+            NameServiceClient::new(Endpoint::from(uri).connect_lazy()),
+        )
+    }
+
+    fn put_request(user: &User) -> proto::PutRequest {
+        put_request_at(user, SystemTime::now())
+    }
+
+    fn put_request_at(user: &User, signed_at: SystemTime) -> proto::PutRequest {
+        proto::PutRequest {
+            account: Some(proto::Account {
+                public_key: bincode::serialize(&user.public_key()).expect("serialize key"),
+                name: user.name.clone(),
+                profile: Some(user.profile.clone().into()),
+                verified: false,
+            }),
+            signature: bincode::serialize(&user.sign_claim(signed_at).expect("sign"))
+                .expect("serialize signature"),
+            signed_at: at2_ns::to_timestamp(signed_at),
+        }
+    }
+
+    #[tokio::test]
+    async fn put_is_returned_by_get_all() {
+        let (mut client, _) = start(Service::new(Accounts::new())).await;
+        let mut user = User::new("user".to_owned(), KeyPair::random());
+        user.profile.bio = Some("hello".to_owned());
+
+        client.put(user.clone()).await.expect("put user");
+
+        assert_eq!(
+            client.get_all().await.expect("get all"),
+            vec![Contact::new(user.name.clone(), user.public_key()).with_profile(user.profile)]
+                .into_iter()
+                .collect::<HashSet<_>>()
+        );
+    }
+
+    #[tokio::test]
+    async fn taken_name_is_rejected() {
+        let (mut client, _) = start(Service::new(Accounts::new())).await;
+
+        client
+            .put(User::new("user".to_owned(), KeyPair::random()))
+            .await
+            .expect("put first user");
+
+        assert!(matches!(
+            client
+                .put(User::new("user".to_owned(), KeyPair::random()))
+                .await,
+            Err(client::Error::NameTaken)
+        ));
+    }
+
+    #[tokio::test]
+    async fn batch_is_put_per_item() {
+        let (mut client, mut raw) = start(Service::new(Accounts::new())).await;
+        let users = vec![
+            User::new("first".to_owned(), KeyPair::random()),
+            User::new("first".to_owned(), KeyPair::random()),
+            User::new("second".to_owned(), KeyPair::random()),
+        ];
+
+        let mut puts = users.iter().map(put_request).collect::<Vec<_>>();
+        puts.push(proto::PutRequest {
+            account: None,
+            signature: Vec::new(),
+            signed_at: 0,
+        });
+        let codes = raw
+            .put_batch(proto::PutBatchRequest {
+                puts,
+                atomic: false,
+            })
+            .await
+            .expect("put batch")
+            .into_inner()
+            .statuses
+            .into_iter()
+            .map(|status| Code::from_i32(status.code))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            codes,
+            vec![
+                Code::Ok,
+                Code::AlreadyExists,
+                Code::Ok,
+                Code::InvalidArgument
+            ]
+        );
+
+        let names = client
+            .get_all()
+            .await
+            .expect("get all")
+            .into_iter()
+            .map(|contact| contact.name)
+            .collect::<HashSet<_>>();
+        assert_eq!(
+            names,
+            vec!["first".to_owned(), "second".to_owned()]
+                .into_iter()
+                .collect()
+        );
+    }
+
+    #[tokio::test]
+    async fn too_large_batch_is_rejected() {
+        let (_, mut raw) = start(Service::new(Accounts::new())).await;
+
+        let puts = (0..=MAX_BATCH)
+            .map(|_| proto::PutRequest {
+                account: None,
+                signature: Vec::new(),
+                signed_at: 0,
+            })
+            .collect();
+        let status = raw
+            .put_batch(proto::PutBatchRequest { puts, atomic: true })
+            .await
+            .expect_err("batch too large");
+
+        assert_eq!(status.code(), Code::InvalidArgument);
+    }
+
+    #[tokio::test]
+    async fn atomic_batch_is_all_or_nothing() {
+        let (mut client, _) = start(Service::new(Accounts::new())).await;
+        let users = vec![
+            User::new("first".to_owned(), KeyPair::random()),
+            User::new("second".to_owned(), KeyPair::random()),
+            User::new("first".to_owned(), KeyPair::random()),
+        ];
+
+        let results = client
+            .put_batch(users.clone(), true)
+            .await
+            .expect("put atomic batch");
+        assert!(matches!(
+            results.as_slice(),
+            [
+                Err(client::Error::Aborted { .. }),
+                Err(client::Error::Aborted { .. }),
+                Err(client::Error::NameTaken),
+            ]
+        ));
+        assert!(client.get_all().await.expect("get all").is_empty());
+
+        let results = client
+            .put_batch(users[..2].to_vec(), true)
+            .await
+            .expect("put atomic batch");
+        assert!(results.iter().all(Result::is_ok));
+        assert_eq!(client.get_all().await.expect("get all").len(), 2);
+    }
+
+    #[tokio::test]
+    async fn malformed_public_key_is_rejected() {
+        let (_, mut raw) = start(Service::new(Accounts::new())).await;
+        let mut request = put_request(&User::new("user".to_owned(), KeyPair::random()));
+        if let Some(account) = request.account.as_mut() {
+            account.public_key = vec![1, 2, 3];
+        }
+
+        let status = raw.put(request).await.expect_err("put malformed key");
+
+        assert_eq!(status.code(), Code::InvalidArgument);
+    }
+
+    #[tokio::test]
+    async fn bad_signature_is_rejected() {
+        let (mut client, mut raw) = start(Service::new(Accounts::new())).await;
+        let user = User::new("user".to_owned(), KeyPair::random());
+
+        // signed for another name
+        let mut request = put_request(&user);
+        if let Some(account) = request.account.as_mut() {
+            account.name = "other".to_owned();
+        }
+        let status = raw.put(request).await.expect_err("put other name");
+        assert_eq!(status.code(), Code::InvalidArgument);
+
+        // signed by another key
+        let mut request = put_request(&User::new(user.name.clone(), KeyPair::random()));
+        request.account = put_request(&user).account;
+        let status = raw.put(request).await.expect_err("put other key");
+        assert_eq!(status.code(), Code::InvalidArgument);
+
+        assert!(client.get_all().await.expect("get all").is_empty());
+    }
+
+    #[tokio::test]
+    async fn unsafe_profile_is_rejected() {
+        let (mut client, _) = start(Service::new(Accounts::new())).await;
+
+        let mut user = User::new("user".to_owned(), KeyPair::random());
+        user.profile.url = Some("javascript:alert(1)".to_owned());
+        assert!(matches!(
+            client.put(user).await,
+            Err(client::Error::InvalidRequest { .. })
+        ));
+
+        let mut user = User::new("user".to_owned(), KeyPair::random());
+        user.profile.bio = Some("a".repeat(Profile::MAX_BIO_LEN + 1));
+        assert!(matches!(
+            client.put(user).await,
+            Err(client::Error::InvalidRequest { .. })
+        ));
+
+        assert!(client.get_all().await.expect("get all").is_empty());
+    }
+
+    #[tokio::test]
+    async fn put_not_signed_recently_is_rejected() {
+        let (mut client, mut raw) = start(Service::new(Accounts::new())).await;
+        let user = User::new("user".to_owned(), KeyPair::random());
+
+        let long_ago = SystemTime::now() - Duration::from_secs(60 * 60);
+        let status = raw
+            .put(put_request_at(&user, long_ago))
+            .await
+            .expect_err("put signed long ago");
+        assert_eq!(status.code(), Code::InvalidArgument);
+
+        // signed for another time
+        let mut request = put_request(&user);
+        request.signed_at -= 1;
+        let status = raw.put(request).await.expect_err("put other time");
+        assert_eq!(status.code(), Code::InvalidArgument);
+
+        assert!(client.get_all().await.expect("get all").is_empty());
+    }
+
+    #[tokio::test]
+    async fn replayed_put_does_not_undo_a_rename() {
+        let (mut client, mut raw) = start(Service::new(Accounts::new())).await;
+        let mut user = User::new("first".to_owned(), KeyPair::random());
+        let now = SystemTime::now();
+
+        let first = put_request_at(&user, now - Duration::from_secs(2));
+        raw.put(first.clone()).await.expect("put first name");
+        user.name = "second".to_owned();
+        raw.put(put_request_at(&user, now - Duration::from_secs(1)))
+            .await
+            .expect("put second name");
+
+        let status = raw.put(first).await.expect_err("replay first name");
+        assert_eq!(status.code(), Code::FailedPrecondition);
+
+        assert_eq!(
+            client.get_all().await.expect("get all"),
+            vec![Contact::new(user.name.clone(), user.public_key())]
+                .into_iter()
+                .collect::<HashSet<_>>()
+        );
+    }
+
+    #[tokio::test]
+    async fn missing_account_is_rejected() {
+        let (_, mut raw) = start(Service::new(Accounts::new())).await;
+        let mut request = put_request(&User::new("user".to_owned(), KeyPair::random()));
+        request.account = None;
+
+        let status = raw.put(request).await.expect_err("put without account");
+
+        assert_eq!(status.code(), Code::InvalidArgument);
+    }
+
+    #[tokio::test]
+    async fn malformed_history_query_is_rejected() {
+        let (_, mut raw) = start(Service::new(Accounts::new())).await;
+
+        let status = raw
+            .get_history(proto::GetHistoryRequest {
+                public_key: vec![1, 2, 3],
+            })
+            .await
+            .expect_err("get history of malformed key");
+
+        assert_eq!(status.code(), Code::InvalidArgument);
+    }
+
+    #[tokio::test]
+    async fn attest_needs_a_known_admin_and_account() {
+        let admin = KeyPair::random();
+        let (mut client, _) = start(
+            Service::new(Accounts::new()).with_admins(vec![admin.public()].into_iter().collect()),
+        )
+        .await;
+        let user = User::new("user".to_owned(), KeyPair::random());
+
+        let attestation = Attestation::new(&admin, user.public_key(), true, SystemTime::now())
+            .expect("sign attestation");
+        assert!(matches!(
+            client.attest(attestation.clone()).await,
+            Err(client::Error::AccountNotFound)
+        ));
+
+        let forged = Attestation::new(
+            &KeyPair::random(),
+            user.public_key(),
+            true,
+            SystemTime::now(),
+        )
+        .expect("sign forged attestation");
+        assert!(matches!(
+            client.attest(forged).await,
+            Err(client::Error::PermissionDenied { .. })
+        ));
+
+        client.put(user.clone()).await.expect("put user");
+        client.attest(attestation).await.expect("attest");
+        assert!(client
+            .get_all()
+            .await
+            .expect("get all")
+            .iter()
+            .all(|contact| contact.verified));
+    }
+
+    #[tokio::test]
+    async fn history_of_unknown_key_is_empty() {
+        let (mut client, _) = start(Service::new(Accounts::new())).await;
+
+        let history = client
+            .get_history(&KeyPair::random().public())
+            .await
+            .expect("get history");
+
+        assert!(history.changes().is_empty());
+    }
 }
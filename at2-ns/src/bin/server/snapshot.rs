@@ -0,0 +1,228 @@
+//! Versioned dump of the directory, to move it between hosts
+
+use std::{
+    io,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use at2_ns::{NameChange, Profile};
+use drop::crypto::sign;
+use serde::{Deserialize, Serialize};
+use snafu::ResultExt;
+
+use super::accounts::{Account, Accounts, State, Verification};
+
+#[derive(snafu::Snafu, Debug)]
+pub enum Error {
+    #[snafu(display("read {}: {}", path.display(), source))]
+    Read { path: PathBuf, source: io::Error },
+    #[snafu(display("write {}: {}", path.display(), source))]
+    Write { path: PathBuf, source: io::Error },
+    #[snafu(display("invalid format: {}", source))]
+    Format { source: serde_json::Error },
+    #[snafu(display("directory: {}", source))]
+    Directory { source: super::accounts::Error },
+}
+
+#[derive(Serialize, Deserialize)]
+struct Entry {
+    public_key: sign::PublicKey,
+    name: String,
+    #[serde(default)]
+    profile: Profile,
+    registered_at: SystemTime,
+    updated_at: SystemTime,
+    /// Missing in snapshots taken before expiry existed
+    #[serde(default)]
+    renewed_at: Option<SystemTime>,
+    #[serde(default)]
+    verification: Option<Verification>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Change {
+    name: String,
+    since: SystemTime,
+}
+
+#[derive(Serialize, Deserialize)]
+struct HistoryEntry {
+    public_key: sign::PublicKey,
+    /// Oldest first
+    changes: Vec<Change>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "version")]
+enum Snapshot {
+    /// Only the current names are known
+    #[serde(rename = "1")]
+    V1 { accounts: Vec<Entry> },
+    #[serde(rename = "2")]
+    V2 {
+        accounts: Vec<Entry>,
+        /// Also of the dropped accounts
+        history: Vec<HistoryEntry>,
+    },
+}
+
+/// Read a snapshot written by [`dump`]
+pub async fn load(path: &Path) -> Result<State, Error> {
+    let content = tokio::fs::read(path).await.context(Read { path })?;
+
+    let (entries, history) = match serde_json::from_slice(&content).context(Format)? {
+        Snapshot::V1 { accounts } => (accounts, Vec::new()),
+        Snapshot::V2 { accounts, history } => (accounts, history),
+    };
+
+    let accounts = entries
+        .into_iter()
+        .map(|entry| {
+            (
+                entry.public_key,
+                Account {
+                    name: entry.name,
+                    profile: entry.profile,
+                    registered_at: entry.registered_at,
+                    updated_at: entry.updated_at,
+                    renewed_at: entry.renewed_at.unwrap_or(entry.updated_at),
+                    verification: entry.verification,
+                },
+            )
+        })
+        .collect();
+    let history = history
+        .into_iter()
+        .map(|entry| {
+            (
+                entry.public_key,
+                entry
+                    .changes
+                    .into_iter()
+                    .map(|change| NameChange {
+                        name: change.name,
+                        since: change.since,
+                    })
+                    .collect(),
+            )
+        })
+        .collect();
+
+    Ok(State { accounts, history })
+}
+
+/// Write the current state of the accounts, replacing the file atomically
+pub async fn dump(path: &Path, accounts: &Accounts) -> Result<(), Error> {
+    write(path, accounts.snapshot().await.context(Directory)?).await
+}
+
+/// Write the given accounts, as when they are already closed
+pub async fn write(path: &Path, state: State) -> Result<(), Error> {
+    let accounts = state
+        .accounts
+        .into_iter()
+        .map(|(public_key, account)| Entry {
+            public_key,
+            name: account.name,
+            profile: account.profile,
+            registered_at: account.registered_at,
+            updated_at: account.updated_at,
+            renewed_at: Some(account.renewed_at),
+            verification: account.verification,
+        })
+        .collect();
+    let history = state
+        .history
+        .into_iter()
+        .map(|(public_key, history)| HistoryEntry {
+            public_key,
+            changes: history
+                .changes()
+                .iter()
+                .map(|change| Change {
+                    name: change.name.clone(),
+                    since: change.since,
+                })
+                .collect(),
+        })
+        .collect();
+    let content = serde_json::to_vec(&Snapshot::V2 { accounts, history }).context(Format)?;
+
+    let tmp_path = path.with_extension("tmp");
+    tokio::fs::write(&tmp_path, content)
+        .await
+        .context(Write { path: &tmp_path })?;
+    tokio::fs::rename(&tmp_path, path)
+        .await
+        .context(Write { path })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, process, time::SystemTime};
+
+    use at2_ns::User;
+    use drop::crypto::sign::KeyPair;
+
+    use super::{dump, load};
+    use crate::accounts::Accounts;
+
+    #[tokio::test]
+    async fn dump_then_load_keeps_accounts() {
+        let path = env::temp_dir().join(format!("at2-ns-snapshot-{}.json", process::id()));
+
+        let accounts = Accounts::new();
+        let mut user = User::new("user".to_owned(), KeyPair::random());
+        user.profile.url = Some("https://example.com".to_owned());
+        accounts
+            .put(
+                user.public_key(),
+                user.name.clone(),
+                user.profile.clone(),
+                SystemTime::now(),
+            )
+            .await
+            .expect("put user");
+
+        dump(&path, &accounts).await.expect("dump");
+        let loaded = load(&path).await.expect("load");
+        let _ = std::fs::remove_file(&path);
+
+        assert_eq!(loaded, accounts.snapshot().await.expect("snapshot"));
+    }
+
+    #[tokio::test]
+    async fn load_first_version() {
+        let path = env::temp_dir().join(format!("at2-ns-snapshot-v1-{}.json", process::id()));
+
+        let accounts = Accounts::new();
+        let user = User::new("user".to_owned(), KeyPair::random());
+        accounts
+            .put(
+                user.public_key(),
+                user.name.clone(),
+                user.profile.clone(),
+                SystemTime::now(),
+            )
+            .await
+            .expect("put user");
+        dump(&path, &accounts).await.expect("dump");
+
+        // as written before the history was kept
+        let mut content: serde_json::Value =
+            serde_json::from_slice(&std::fs::read(&path).expect("read")).expect("parse");
+        content["version"] = "1".into();
+        content.as_object_mut().expect("object").remove("history");
+        std::fs::write(&path, content.to_string()).expect("write");
+
+        let loaded = load(&path).await.expect("load");
+        let _ = std::fs::remove_file(&path);
+
+        assert_eq!(
+            loaded.accounts,
+            accounts.snapshot().await.expect("snapshot").accounts
+        );
+        assert!(loaded.history.is_empty());
+    }
+}
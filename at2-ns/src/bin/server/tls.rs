@@ -0,0 +1,226 @@
+//! Serve over TLS, with a certificate which can be reloaded
+
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    sync::{Arc, RwLock},
+    time::Duration,
+};
+
+use snafu::{OptionExt, ResultExt};
+use tokio::{
+    net::{TcpListener, TcpStream},
+    sync::mpsc,
+    time,
+};
+use tokio_rustls::{
+    rustls::{internal::pemfile, Certificate, NoClientAuth, PrivateKey, ServerConfig, TLSError},
+    server::TlsStream,
+    TlsAcceptor,
+};
+use tokio_stream::wrappers::ReceiverStream;
+
+/// Connections not done with the handshake by then are dropped
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(snafu::Snafu, Debug)]
+pub enum Error {
+    #[snafu(display("read {}: {}", path.display(), source))]
+    Read { path: PathBuf, source: io::Error },
+    #[snafu(display("no certificate found in {}", path.display()))]
+    NoCertificate { path: PathBuf },
+    #[snafu(display("no private key found in {}", path.display()))]
+    NoKey { path: PathBuf },
+    #[snafu(display("invalid certificate or key: {}", source))]
+    Invalid { source: TLSError },
+}
+
+fn read_certificates(path: &Path) -> Result<Vec<Certificate>, Error> {
+    let content = fs::read(path).context(Read { path })?;
+
+    pemfile::certs(&mut content.as_slice())
+        .ok()
+        .filter(|certificates| !certificates.is_empty())
+        .context(NoCertificate { path })
+}
+
+fn read_key(path: &Path) -> Result<PrivateKey, Error> {
+    let content = fs::read(path).context(Read { path })?;
+
+    pemfile::pkcs8_private_keys(&mut content.as_slice())
+        .ok()
+        .and_then(|keys| keys.into_iter().next())
+        .or_else(|| {
+            pemfile::rsa_private_keys(&mut content.as_slice())
+                .ok()
+                .and_then(|keys| keys.into_iter().next())
+        })
+        .context(NoKey { path })
+}
+
+fn read_config(certificate: &Path, key: &Path) -> Result<Arc<ServerConfig>, Error> {
+    let mut config = ServerConfig::new(NoClientAuth::new());
+    config
+        .set_single_cert(read_certificates(certificate)?, read_key(key)?)
+        .context(Invalid)?;
+    // gRPC-web is sent over HTTP/1
+    config.set_protocols(&[b"h2".to_vec(), b"http/1.1".to_vec()]);
+
+    Ok(Arc::new(config))
+}
+
+#[derive(Clone)]
+pub struct Tls {
+    certificate: PathBuf,
+    key: PathBuf,
+    config: Arc<RwLock<Arc<ServerConfig>>>,
+}
+
+impl Tls {
+    /// Read the PEM encoded certificate chain and private key
+    pub fn load(certificate: PathBuf, key: PathBuf) -> Result<Self, Error> {
+        let config = read_config(&certificate, &key)?;
+
+        Ok(Self {
+            certificate,
+            key,
+            config: Arc::new(RwLock::new(config)),
+        })
+    }
+
+    /// Read the files again, for the next connections, keeping the current ones on failure
+    pub fn reload(&self) -> Result<(), Error> {
+        let config = read_config(&self.certificate, &self.key)?;
+        *self.config.write().expect("not poisoned") = config;
+
+        Ok(())
+    }
+
+    fn acceptor(&self) -> TlsAcceptor {
+        TlsAcceptor::from(self.config.read().expect("not poisoned").clone())
+    }
+
+    /// Accept the connections of the listener, doing the handshakes in parallel
+    pub fn incoming(
+        self,
+        listener: TcpListener,
+    ) -> ReceiverStream<io::Result<TlsStream<TcpStream>>> {
+        let (tx, rx) = mpsc::channel(32);
+
+        tokio::spawn(async move {
+            loop {
+                let stream = match listener.accept().await {
+                    Ok((stream, _)) => stream,
+                    Err(err) => {
+                        // stops the server
+                        let _ = tx.send(Err(err)).await;
+                        return;
+                    }
+                };
+
+                let (acceptor, tx) = (self.acceptor(), tx.clone());
+                tokio::spawn(async move {
+                    match time::timeout(HANDSHAKE_TIMEOUT, acceptor.accept(stream)).await {
+                        Ok(Ok(stream)) => {
+                            let _ = tx.send(Ok(stream)).await;
+                        }
+                        Ok(Err(err)) => tracing::debug!("TLS handshake failed: {}", err),
+                        Err(_) => tracing::debug!("TLS handshake timed out"),
+                    }
+                });
+            }
+        });
+
+        ReceiverStream::new(rx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, net::SocketAddr, path::PathBuf, process, sync::Arc};
+
+    use tokio::net::{TcpListener, TcpStream};
+    use tokio_rustls::{rustls::ClientConfig, webpki::DNSNameRef, TlsConnector};
+    use tokio_stream::StreamExt;
+
+    use super::{Error, Tls};
+
+    /// Self-signed certificate for localhost, with its key, both PEM encoded
+    fn generate() -> (String, String) {
+        let certificate =
+            rcgen::generate_simple_self_signed(vec!["localhost".to_owned()]).expect("generate");
+
+        (
+            certificate.serialize_pem().expect("serialize certificate"),
+            certificate.serialize_private_key_pem(),
+        )
+    }
+
+    fn write(name: &str, (certificate, key): &(String, String)) -> (PathBuf, PathBuf) {
+        let path =
+            |kind| env::temp_dir().join(format!("at2-ns-{}-{}-{}.pem", name, kind, process::id()));
+        let (certificate_path, key_path) = (path("certificate"), path("key"));
+
+        std::fs::write(&certificate_path, certificate).expect("write certificate");
+        std::fs::write(&key_path, key).expect("write key");
+
+        (certificate_path, key_path)
+    }
+
+    /// If a client only trusting the given certificate can connect
+    async fn handshake(address: SocketAddr, trusted: &str) -> bool {
+        let mut config = ClientConfig::new();
+        config
+            .root_store
+            .add_pem_file(&mut trusted.as_bytes())
+            .expect("add trusted certificate");
+
+        let stream = TcpStream::connect(address).await.expect("connect");
+        TlsConnector::from(Arc::new(config))
+            .connect(
+                DNSNameRef::try_from_ascii_str("localhost").expect("valid name"),
+                stream,
+            )
+            .await
+            .is_ok()
+    }
+
+    #[tokio::test]
+    async fn reload_serves_the_new_certificate() {
+        let (old, new) = (generate(), generate());
+        let (certificate_path, key_path) = write("reload", &old);
+
+        let tls = Tls::load(certificate_path, key_path).expect("load");
+        let listener = TcpListener::bind("127.0.0.1:0").await.expect("bind");
+        let address = listener.local_addr().expect("local address");
+        let mut incoming = tls.clone().incoming(listener);
+        tokio::spawn(async move { while incoming.next().await.is_some() {} });
+
+        assert!(handshake(address, &old.0).await);
+        assert!(!handshake(address, &new.0).await);
+
+        let (certificate_path, key_path) = write("reload", &new);
+        tls.reload().expect("reload");
+        let _ = std::fs::remove_file(certificate_path);
+        let _ = std::fs::remove_file(key_path);
+
+        assert!(handshake(address, &new.0).await);
+        assert!(!handshake(address, &old.0).await);
+    }
+
+    #[test]
+    fn missing_or_invalid_files_are_reported() {
+        let path = env::temp_dir().join(format!("at2-ns-tls-{}.pem", process::id()));
+
+        assert!(matches!(
+            Tls::load(path.clone(), path.clone()),
+            Err(Error::Read { .. })
+        ));
+
+        std::fs::write(&path, "not a certificate").expect("write");
+        let ret = Tls::load(path.clone(), path.clone());
+        let _ = std::fs::remove_file(&path);
+
+        assert!(matches!(ret, Err(Error::NoCertificate { .. })));
+    }
+}
@@ -1,13 +1,19 @@
 //! Client to connect to the name service
 
-use std::collections::HashSet;
+use std::{collections::HashSet, time::SystemTime};
 
+use drop::crypto::sign;
 use http::Uri;
 use snafu::{ResultExt, Snafu};
+use tonic::{
+    body::BoxBody,
+    client::GrpcService,
+    codegen::{Body, StdError},
+};
 
 use crate::{
     proto::{name_service_client::NameServiceClient, *},
-    User, Contact,
+    Attestation, Contact, History, User,
 };
 
 /// Errors generated by the [`Client`]
@@ -28,6 +34,36 @@ pub enum Error {
         /// Source of the error
         source: drop::crypto::sign::SignError,
     },
+    /// Name already used by another account
+    #[snafu(display("name taken"))]
+    NameTaken,
+    /// No account registered for this public key
+    #[snafu(display("account not found"))]
+    AccountNotFound,
+    /// Request refused by the server
+    #[snafu(display("permission denied: {}", message))]
+    PermissionDenied {
+        /// Reason given by the server
+        message: String,
+    },
+    /// Request considered malformed by the server
+    #[snafu(display("invalid request: {}", message))]
+    InvalidRequest {
+        /// Reason given by the server
+        message: String,
+    },
+    /// Not done as another part of the request failed
+    #[snafu(display("aborted: {}", message))]
+    Aborted {
+        /// Reason given by the server
+        message: String,
+    },
+    /// Server not reachable or not ready, retrying later might work
+    #[snafu(display("unavailable: {}", source.message()))]
+    Unavailable {
+        /// Source of the error
+        source: tonic::Status,
+    },
     /// Communicating with the server
     #[snafu(display("server answered: {}", source.message()))]
     Rpc {
@@ -36,13 +72,71 @@ pub enum Error {
     },
 }
 
+impl From<tonic::Status> for Error {
+    fn from(status: tonic::Status) -> Self {
+        use tonic::Code;
+
+        match status.code() {
+            Code::AlreadyExists => Self::NameTaken,
+            Code::NotFound => Self::AccountNotFound,
+            Code::PermissionDenied => Self::PermissionDenied {
+                message: status.message().to_owned(),
+            },
+            Code::InvalidArgument => Self::InvalidRequest {
+                message: status.message().to_owned(),
+            },
+            Code::Aborted => Self::Aborted {
+                message: status.message().to_owned(),
+            },
+            Code::Unavailable => Self::Unavailable { source: status },
+            _ => Self::Rpc { source: status },
+        }
+    }
+}
+
 type Result<T> = std::result::Result<T, Error>;
 
-/// wasm only gRPC web client
+/// Current time, as [`SystemTime::now`] isn't available in the browser
+#[cfg(target_arch = "wasm32")]
+fn now() -> SystemTime {
+    std::time::UNIX_EPOCH + std::time::Duration::from_secs_f64(js_sys::Date::now() / 1000.0)
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn now() -> SystemTime {
+    SystemTime::now()
+}
+
+fn put_request(user: &User) -> Result<PutRequest> {
+    let signed_at = now();
+
+    Ok(PutRequest {
+        account: Some(Account {
+            public_key: bincode::serialize(&user.public_key()).context(Serialize)?,
+            name: user.name.to_owned(),
+            profile: Some(user.profile.clone().into()),
+            verified: false,
+        }),
+        signature: bincode::serialize(&user.sign_claim(signed_at).context(Signature)?)
+            .context(Serialize)?,
+        signed_at: crate::to_timestamp(signed_at),
+    })
+}
+
+/// gRPC client, generic over the underlying transport
 #[derive(Clone)]
-pub struct Client(NameServiceClient<grpc_web_client::Client>);
+pub struct Client<T>(NameServiceClient<T>);
+
+/// wasm only gRPC web client
+#[cfg(feature = "client")]
+pub type WebClient = Client<grpc_web_client::Client>;
 
-impl Client {
+/// Native gRPC client, using tonic's own transport
+#[cfg(feature = "native-client")]
+pub type NativeClient = Client<tonic::transport::Channel>;
+
+#[cfg(feature = "client")]
+impl Client<grpc_web_client::Client> {
     /// Create a new Client connecting to the given [`Uri`]
     pub fn new(uri: &Uri) -> Self {
         let mut url_string = uri.to_string();
@@ -55,37 +149,107 @@ impl Client {
             url_string,
         )))
     }
+}
 
-    /// Put a new user, updating its name if already existing
+#[cfg(feature = "native-client")]
+impl Client<tonic::transport::Channel> {
+    /// Create a new Client connecting to the given [`Uri`]
+    ///
+    /// The connection is only established on the first request, it has to be
+    /// called inside of a tokio runtime.
+    pub fn new(uri: &Uri) -> Self {
+        Self(NameServiceClient::new(
+            tonic::transport::Endpoint::from(uri.to_owned()).connect_lazy(),
+        ))
+    }
+}
+
+impl<T> Client<T>
+where
+    T: GrpcService<BoxBody>,
+    T::ResponseBody: Body + Send + 'static,
+    T::Error: Into<StdError>,
+    <T::ResponseBody as Body>::Error: Into<StdError> + Send,
+{
+    /// Create a new Client over an already setup transport
+    pub fn with_transport(transport: T) -> Self {
+        Self(NameServiceClient::new(transport))
+    }
+
+    /// Put a new user, updating its name and profile if already existing
     pub async fn put(&mut self, user: User) -> Result<()> {
         self.0
-            .put(PutRequest {
-                account: Some(Account {
-                    public_key: bincode::serialize(&user.public_key()).context(Serialize)?,
-                    name: user.name.to_owned(),
-                }),
-                signature: bincode::serialize(&user.keypair().sign(&user.name).context(Signature)?)
-                    .context(Serialize)?,
-            })
+            .put(put_request(&user)?)
             .await
-            .context(Rpc)
             .map(|_| {})
+            .map_err(Into::into)
+    }
+
+    /// Put many users at once, returning the result of each, in the same order
+    ///
+    /// If atomic, either every user is put or none.
+    pub async fn put_batch(&mut self, users: Vec<User>, atomic: bool) -> Result<Vec<Result<()>>> {
+        let puts = users.iter().map(put_request).collect::<Result<_>>()?;
+
+        let reply = self.0.put_batch(PutBatchRequest { puts, atomic }).await?;
+
+        Ok(reply
+            .into_inner()
+            .statuses
+            .into_iter()
+            .map(|status| match tonic::Code::from_i32(status.code) {
+                tonic::Code::Ok => Ok(()),
+                code => Err(tonic::Status::new(code, status.message).into()),
+            })
+            .collect())
     }
 
     /// Get all the registered users
     pub async fn get_all(&mut self) -> Result<HashSet<Contact>> {
-        let reply = self.0.get_all(GetAllRequest {}).await.context(Rpc)?;
+        let reply = self.0.get_all(GetAllRequest {}).await?;
 
         reply
             .into_inner()
             .accounts
-            .iter()
+            .into_iter()
             .map(|account| {
                 Ok(Contact::new(
-                    account.name.clone(),
+                    account.name,
                     bincode::deserialize(&account.public_key).context(Deserialize)?,
-                ))
+                )
+                .with_profile(account.profile.map(Into::into).unwrap_or_default())
+                .with_verified(account.verified))
             })
             .collect::<Result<HashSet<_>>>()
     }
+
+    /// Mark a registered public key as official, or not anymore
+    ///
+    /// The attestation has to be signed by one of the admins of the server.
+    pub async fn attest(&mut self, attestation: Attestation) -> Result<()> {
+        self.0
+            .attest(AttestRequest {
+                attestation: Some(attestation.try_into().context(Serialize)?),
+            })
+            .await
+            .map(|_| {})
+            .map_err(Into::into)
+    }
+
+    /// Get every name used by a public key, empty if never registered
+    pub async fn get_history(&mut self, public_key: &sign::PublicKey) -> Result<History> {
+        let reply = self
+            .0
+            .get_history(GetHistoryRequest {
+                public_key: bincode::serialize(public_key).context(Serialize)?,
+            })
+            .await?;
+
+        Ok(reply
+            .into_inner()
+            .changes
+            .into_iter()
+            .map(crate::NameChange::from)
+            .collect())
+    }
 }
@@ -1,5 +1,7 @@
 use drop::crypto::sign;
 
+use super::Profile;
+
 /// Other users on the network
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
@@ -7,12 +9,35 @@ pub struct Contact {
     /// Name of this user
     pub name: String,
     public_key: sign::PublicKey,
+    /// What the user shares about itself
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub profile: Profile,
+    /// Attested as official by an admin of the name service
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub verified: bool,
 }
 
 impl Contact {
     /// New contact
     pub fn new(name: String, public_key: sign::PublicKey) -> Self {
-        Self { name, public_key }
+        Self {
+            name,
+            public_key,
+            profile: Profile::default(),
+            verified: false,
+        }
+    }
+
+    /// Set the profile
+    pub fn with_profile(mut self, profile: Profile) -> Self {
+        self.profile = profile;
+        self
+    }
+
+    /// Set if attested as official
+    pub fn with_verified(mut self, verified: bool) -> Self {
+        self.verified = verified;
+        self
     }
 
     /// Return the public key
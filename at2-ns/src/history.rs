@@ -0,0 +1,84 @@
+use std::time::SystemTime;
+
+/// Name used by a public key from a given time
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NameChange {
+    /// Name taken
+    pub name: String,
+    /// When it was taken
+    pub since: SystemTime,
+}
+
+/// Every name used by a public key, oldest first
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct History(Vec<NameChange>);
+
+impl History {
+    /// Start the history with a single name
+    pub fn new(name: String, since: SystemTime) -> Self {
+        Self(vec![NameChange { name, since }])
+    }
+
+    /// Record that the name was taken at the given time
+    ///
+    /// Changes can be recorded out of order, as received from other instances.
+    /// Taking the same name as the previous change isn't recorded.
+    pub fn record(&mut self, name: String, since: SystemTime) {
+        let index = self.0.partition_point(|change| change.since <= since);
+
+        let same_as_previous = index
+            .checked_sub(1)
+            .and_then(|previous| self.0.get(previous))
+            .map_or(false, |previous| previous.name == name);
+        if !same_as_previous {
+            self.0.insert(index, NameChange { name, since });
+        }
+    }
+
+    /// Changes, oldest first
+    pub fn changes(&self) -> &[NameChange] {
+        &self.0
+    }
+
+    /// Name used at the given time, `None` if not yet registered
+    pub fn name_at(&self, time: SystemTime) -> Option<&str> {
+        self.0
+            .iter()
+            .take_while(|change| change.since <= time)
+            .last()
+            .map(|change| change.name.as_str())
+    }
+
+    /// Name currently used
+    pub fn current(&self) -> Option<&str> {
+        self.0.last().map(|change| change.name.as_str())
+    }
+}
+
+impl FromIterator<NameChange> for History {
+    fn from_iter<T: IntoIterator<Item = NameChange>>(iter: T) -> Self {
+        let mut history = Self::default();
+        iter.into_iter()
+            .for_each(|change| history.record(change.name, change.since));
+
+        history
+    }
+}
+
+impl From<crate::proto::NameChange> for NameChange {
+    fn from(change: crate::proto::NameChange) -> Self {
+        Self {
+            name: change.name,
+            since: crate::from_timestamp(change.since),
+        }
+    }
+}
+
+impl From<NameChange> for crate::proto::NameChange {
+    fn from(change: NameChange) -> Self {
+        Self {
+            name: change.name,
+            since: crate::to_timestamp(change.since),
+        }
+    }
+}
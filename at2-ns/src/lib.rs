@@ -6,10 +6,23 @@
 #[allow(missing_docs)]
 pub mod proto;
 
+#[cfg(any(feature = "client", feature = "native-client"))]
 pub mod client;
 
 mod user;
 pub use user::User;
 
+mod attestation;
+pub use attestation::Attestation;
+
 mod contact;
 pub use contact::Contact;
+
+mod history;
+pub use history::{History, NameChange};
+
+mod profile;
+pub use profile::Profile;
+
+mod timestamp;
+pub use timestamp::{from_timestamp, to_timestamp};
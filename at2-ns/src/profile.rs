@@ -0,0 +1,103 @@
+use std::time::SystemTime;
+
+use drop::crypto::sign;
+
+use crate::to_timestamp;
+
+/// Optional public information about a user
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Profile {
+    /// Hash of the avatar image, or a small one as a data URL
+    pub avatar: Option<String>,
+    /// Short description
+    pub bio: Option<String>,
+    /// Link to more about the user
+    pub url: Option<String>,
+}
+
+/// Only web links can be followed, others could run scripts in the page
+fn is_web_link(url: &str) -> bool {
+    let url = url.to_ascii_lowercase();
+
+    url.starts_with("https://") || url.starts_with("http://")
+}
+
+impl Profile {
+    /// Longest avatar accepted, in bytes, enough for a small inlined image
+    pub const MAX_AVATAR_LEN: usize = 16 * 1024;
+    /// Longest bio accepted, in bytes
+    pub const MAX_BIO_LEN: usize = 512;
+    /// Longest url accepted, in bytes
+    pub const MAX_URL_LEN: usize = 2048;
+
+    /// If nothing is set
+    pub fn is_empty(&self) -> bool {
+        self.avatar.is_none() && self.bio.is_none() && self.url.is_none()
+    }
+
+    /// If every field is small enough and the url is a web link
+    pub fn is_valid(&self) -> bool {
+        let fits = |field: &Option<String>, max: usize| {
+            field.as_ref().map_or(true, |value| value.len() <= max)
+        };
+
+        fits(&self.avatar, Self::MAX_AVATAR_LEN)
+            && fits(&self.bio, Self::MAX_BIO_LEN)
+            && fits(&self.url, Self::MAX_URL_LEN)
+            && self.url.as_deref().map_or(true, is_web_link)
+    }
+
+    /// Url, only if it is a web link, so safe to follow
+    pub fn safe_url(&self) -> Option<&str> {
+        self.url.as_deref().filter(|url| is_web_link(url))
+    }
+
+    /// Sign the claim of a name with this profile, at the given time
+    ///
+    /// The time is part of the signature, so that the claim can't be
+    /// backdated, nor replayed later, by a server.
+    pub fn sign(
+        &self,
+        keypair: &sign::KeyPair,
+        name: &str,
+        signed_at: SystemTime,
+    ) -> Result<sign::Signature, sign::SignError> {
+        keypair.sign(&(name, self, to_timestamp(signed_at)))
+    }
+
+    /// Verify a signature created by [`Profile::sign`]
+    pub fn verify(
+        &self,
+        signature: &sign::Signature,
+        public_key: &sign::PublicKey,
+        name: &str,
+        signed_at: SystemTime,
+    ) -> Result<(), sign::VerifyError> {
+        signature.verify(&(name, self, to_timestamp(signed_at)), public_key)
+    }
+}
+
+fn non_empty(value: String) -> Option<String> {
+    Some(value).filter(|value| !value.is_empty())
+}
+
+impl From<crate::proto::Profile> for Profile {
+    fn from(profile: crate::proto::Profile) -> Self {
+        Self {
+            avatar: non_empty(profile.avatar),
+            bio: non_empty(profile.bio),
+            url: non_empty(profile.url),
+        }
+    }
+}
+
+impl From<Profile> for crate::proto::Profile {
+    fn from(profile: Profile) -> Self {
+        Self {
+            avatar: profile.avatar.unwrap_or_default(),
+            bio: profile.bio.unwrap_or_default(),
+            url: profile.url.unwrap_or_default(),
+        }
+    }
+}
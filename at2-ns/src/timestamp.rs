@@ -0,0 +1,13 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Nanoseconds since UNIX epoch, as sent on the wire and signed
+pub fn to_timestamp(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos() as u64
+}
+
+/// Inverse of [`to_timestamp`]
+pub fn from_timestamp(timestamp: u64) -> SystemTime {
+    UNIX_EPOCH + Duration::from_nanos(timestamp)
+}
@@ -1,6 +1,8 @@
+use std::time::SystemTime;
+
 use drop::crypto::sign;
 
-use super::Contact;
+use super::{Contact, Profile};
 
 /// Current user
 #[derive(Debug, Clone, PartialEq)]
@@ -9,12 +11,19 @@ pub struct User {
     /// Name of this user
     pub name: String,
     keypair: sign::KeyPair,
+    /// What to share with the other users
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub profile: Profile,
 }
 
 impl User {
     /// Create a new [`User`]
     pub fn new(name: String, keypair: sign::KeyPair) -> Self {
-        Self { name, keypair }
+        Self {
+            name,
+            keypair,
+            profile: Profile::default(),
+        }
     }
 
     /// Return the public key
@@ -27,8 +36,13 @@ impl User {
         &self.keypair
     }
 
+    /// Sign the claim of its name and profile, at the given time
+    pub fn sign_claim(&self, signed_at: SystemTime) -> Result<sign::Signature, sign::SignError> {
+        self.profile.sign(&self.keypair, &self.name, signed_at)
+    }
+
     /// Return a thin version of the user
     pub fn to_thin(self) -> Contact {
-        Contact::new(self.name, self.keypair.public())
+        Contact::new(self.name, self.keypair.public()).with_profile(self.profile)
     }
 }
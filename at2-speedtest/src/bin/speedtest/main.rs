@@ -0,0 +1,218 @@
+use std::{collections::HashSet, fs, io, path::PathBuf, process, time::Duration};
+
+use at2_node::client::{self, Client};
+use at2_ns::{Contact, User};
+use at2_speedtest::{Speedtest, State, Transfer};
+use chrono::Utc;
+use http::Uri;
+use snafu::{OptionExt, ResultExt};
+use structopt::StructOpt;
+use tokio::{signal, sync::mpsc, time};
+
+const TRANSFER_PER_REFRESH: usize = 50;
+
+/// Flood an AT2 network with transactions, printing the progress as JSON lines
+#[derive(structopt::StructOpt)]
+struct Arguments {
+    /// URI of the node to send the transactions to
+    #[structopt(long)]
+    node: Uri,
+    /// Local name service, a JSON list of contacts
+    #[structopt(long)]
+    directory: PathBuf,
+    /// Name of the account to send to, as found in the directory
+    #[structopt(long)]
+    recipient: String,
+    /// Account sending the transactions, as JSON, it has to own enough assets
+    #[structopt(long)]
+    user: PathBuf,
+
+    /// How many transactions to send
+    #[structopt(long, default_value = "1000")]
+    transactions: usize,
+    /// How many accounts are sending in parallel
+    #[structopt(long, default_value = "1")]
+    senders: usize,
+    /// Milliseconds between two reports
+    #[structopt(long, default_value = "1000")]
+    report_interval: u64,
+}
+
+#[derive(Debug, snafu::Snafu)]
+enum Error {
+    #[snafu(display("read {}: {}", path.display(), source))]
+    Read { path: PathBuf, source: io::Error },
+    #[snafu(display("parse {}: {}", path.display(), source))]
+    Parse {
+        path: PathBuf,
+        source: serde_json::Error,
+    },
+    #[snafu(display("unknown recipient: {}", name))]
+    UnknownRecipient { name: String },
+    #[snafu(display("node: {}", source))]
+    Node { source: client::Error },
+    #[snafu(display("report: {}", source))]
+    Report { source: serde_json::Error },
+}
+
+enum Event {
+    Sent {
+        sender: usize,
+        success: bool,
+    },
+    FundingSent {
+        sender: usize,
+        success: bool,
+    },
+    MainSequence(sieve::Sequence),
+    Sequence {
+        sender: usize,
+        sequence: sieve::Sequence,
+    },
+}
+
+fn read_json<T: serde::de::DeserializeOwned>(path: PathBuf) -> Result<T, Error> {
+    let content = fs::read(&path).context(Read { path: path.clone() })?;
+
+    serde_json::from_slice(&content).context(Parse { path })
+}
+
+fn report(speedtest: &Speedtest) -> Result<(), Error> {
+    println!(
+        "{}",
+        serde_json::to_string(&speedtest.metrics(Utc::now())).context(Report)?
+    );
+
+    Ok(())
+}
+
+fn send(
+    client: &Client,
+    transfer: Transfer,
+    events: &mpsc::UnboundedSender<Event>,
+    to_event: impl FnOnce(usize, bool) -> Event + Send + 'static,
+) {
+    let (mut client, events) = (client.clone(), events.clone());
+
+    tokio::spawn(async move {
+        let ret = client
+            .send_asset(
+                transfer.user.keypair(),
+                transfer.sequence,
+                transfer.recipient.public_key().to_owned(),
+                transfer.amount,
+            )
+            .await;
+
+        if let Err(err) = &ret {
+            eprintln!("unable to send transaction: {}", err);
+        }
+
+        let _ = events.send(to_event(transfer.sender, ret.is_ok()));
+    });
+}
+
+fn fetch_sequence(
+    client: &Client,
+    user: &User,
+    events: &mpsc::UnboundedSender<Event>,
+    to_event: impl FnOnce(sieve::Sequence) -> Event + Send + 'static,
+) {
+    let (mut client, public_key, events) = (client.clone(), user.public_key(), events.clone());
+
+    tokio::spawn(async move {
+        if let Ok(sequence) = client.get_last_sequence(&public_key).await {
+            let _ = events.send(to_event(sequence));
+        }
+    });
+}
+
+async fn run(arguments: Arguments) -> Result<(), Error> {
+    let directory: HashSet<Contact> = read_json(arguments.directory)?;
+    let recipient = directory
+        .into_iter()
+        .find(|contact| contact.name == arguments.recipient)
+        .context(UnknownRecipient {
+            name: arguments.recipient,
+        })?;
+
+    let user: User = read_json(arguments.user)?;
+
+    let mut client = Client::new(arguments.node);
+    let sequence = client
+        .get_last_sequence(&user.public_key())
+        .await
+        .context(Node)?;
+
+    let (mut speedtest, funding) = Speedtest::start(
+        (user, sequence),
+        recipient,
+        arguments.transactions,
+        arguments.senders,
+        Utc::now(),
+    );
+
+    let (events, mut events_rx) = mpsc::unbounded_channel();
+    for transfer in funding {
+        send(&client, transfer, &events, |sender, success| {
+            Event::FundingSent { sender, success }
+        });
+    }
+
+    let mut refresher = time::interval(Duration::from_millis(100));
+    let mut reporter = time::interval(Duration::from_millis(arguments.report_interval));
+    let ctrl_c = signal::ctrl_c();
+    tokio::pin!(ctrl_c);
+    let mut cancelled = false;
+
+    while !speedtest.is_done() {
+        tokio::select! {
+            _ = refresher.tick() => match speedtest.state() {
+                State::Funding { .. } | State::CancellingFunding => {
+                    fetch_sequence(&client, speedtest.main_user(), &events, Event::MainSequence)
+                }
+                _ => {
+                    for transfer in speedtest.next_transfers(TRANSFER_PER_REFRESH) {
+                        send(&client, transfer, &events, |sender, success| {
+                            Event::Sent { sender, success }
+                        });
+                    }
+
+                    for (index, sender) in speedtest.senders().iter().enumerate() {
+                        fetch_sequence(&client, sender.user(), &events, move |sequence| {
+                            Event::Sequence { sender: index, sequence }
+                        });
+                    }
+                }
+            },
+            Some(event) = events_rx.recv() => match event {
+                Event::Sent { sender, success } => speedtest.transfer_sent(sender, success),
+                Event::FundingSent { sender, success } => speedtest.funding_sent(sender, success),
+                Event::MainSequence(sequence) => {
+                    speedtest.got_main_sequence(sequence, Utc::now());
+                }
+                Event::Sequence { sender, sequence } => {
+                    speedtest.got_sequence(sender, sequence, Utc::now());
+                }
+            },
+            _ = reporter.tick() => report(&speedtest)?,
+            _ = &mut ctrl_c, if !cancelled => {
+                eprintln!("cancelling, waiting for the sent transactions to be processed");
+                speedtest.cancel(Utc::now());
+                cancelled = true;
+            }
+        }
+    }
+
+    report(&speedtest)
+}
+
+#[tokio::main(flavor = "multi_thread")]
+async fn main() {
+    let arguments = Arguments::from_args();
+
+    run(arguments).await.unwrap_or_else(|err| {
+        eprintln!("error running cmd: {}", err);
+        process::exit(1);
+    });
+}
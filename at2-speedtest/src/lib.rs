@@ -0,0 +1,730 @@
+#![deny(missing_docs)]
+
+//! Speedtest for AT2, flooding the network with transactions
+//!
+//! It only keeps track of the test, sending the transactions and fetching the
+//! sequences is left to the caller, so it can be driven by the web demonstrator
+//! as well as by a terminal.
+
+use at2_ns::{Contact, User};
+use chrono::{DateTime, Duration, Utc};
+use drop::crypto::sign;
+use serde::Serialize;
+
+/// Account flooding the network
+#[derive(Debug, Clone)]
+pub struct Sender {
+    user: User,
+    /// Last sequence used by this account before the speedtest
+    base_sequence: sieve::Sequence,
+
+    scheduled_tx: usize,
+    sent_tx: usize,
+    failed_tx: usize,
+    confirmed_tx: usize,
+    total_tx: usize,
+    /// A send failed, so the following sequences can't be processed anymore
+    stalled: bool,
+}
+
+impl Sender {
+    fn new(user: User, base_sequence: sieve::Sequence, total_tx: usize) -> Self {
+        Self {
+            user,
+            base_sequence,
+
+            scheduled_tx: 0,
+            sent_tx: 0,
+            failed_tx: 0,
+            confirmed_tx: 0,
+            total_tx,
+            stalled: false,
+        }
+    }
+
+    /// Account used to send
+    pub fn user(&self) -> &User {
+        &self.user
+    }
+
+    /// Count of transactions sent to the node
+    pub fn sent_tx(&self) -> usize {
+        self.sent_tx
+    }
+
+    /// Count of transactions the node failed to receive, or which can't be processed anymore
+    pub fn failed_tx(&self) -> usize {
+        if self.is_stalled_out() {
+            self.total_tx - self.confirmed_tx
+        } else {
+            self.failed_tx
+        }
+    }
+
+    /// Count of transactions processed by the network
+    pub fn confirmed_tx(&self) -> usize {
+        self.confirmed_tx
+    }
+
+    /// Count of transactions to send
+    pub fn total_tx(&self) -> usize {
+        self.total_tx
+    }
+
+    /// Stalled and not waiting for any send, so no more transactions will be processed
+    fn is_stalled_out(&self) -> bool {
+        self.stalled && self.sent_tx == self.scheduled_tx
+    }
+
+    /// Every transaction was processed, or can't be anymore
+    fn is_finished(&self) -> bool {
+        self.confirmed_tx == self.total_tx || self.is_stalled_out()
+    }
+
+    /// Every transaction sent is either rejected or processed by the network
+    fn is_settled(&self) -> bool {
+        self.is_stalled_out()
+            || (self.sent_tx == self.scheduled_tx
+                && self.confirmed_tx + self.failed_tx >= self.sent_tx)
+    }
+}
+
+/// Transaction to send on the network
+#[derive(Debug, Clone)]
+pub struct Transfer {
+    /// Index of the sender, or of the funded one when funding, to give back to
+    /// [`Speedtest::transfer_sent`] or [`Speedtest::funding_sent`]
+    pub sender: usize,
+    /// Account sending it
+    pub user: User,
+    /// Sequence to use
+    pub sequence: sieve::Sequence,
+    /// Account receiving it
+    pub recipient: Contact,
+    /// Amount of assets to transfer
+    pub amount: u64,
+}
+
+/// Step of the speedtest
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum State {
+    /// Waiting for the main account to credit the ephemeral ones
+    Funding {
+        /// Sequence of the main account once every sender is credited
+        funded_sequence: sieve::Sequence,
+    },
+    /// Cancelled while funding, waiting for the sent credits to be processed
+    CancellingFunding,
+    /// Flooding the network
+    Started {
+        /// When it was started, minus the time it was paused
+        started_at: DateTime<Utc>,
+    },
+    /// Not sending anymore until resumed
+    Paused {
+        /// Time spent running
+        elapsed: Duration,
+    },
+    /// Waiting for the transactions in flight to be processed
+    Cancelling {
+        /// Time spent running
+        elapsed: Duration,
+    },
+    /// Every transaction was processed
+    Done {
+        /// Time spent running
+        elapsed: Duration,
+        /// If it was stopped before sending everything
+        cancelled: bool,
+    },
+}
+
+/// Progress of a single sender
+#[derive(Debug, Clone, Serialize)]
+pub struct SenderMetrics {
+    /// Name of the account
+    pub name: String,
+    /// Count of transactions sent
+    pub sent_tx: usize,
+    /// Count of transactions the node failed to receive, or which can't be processed anymore
+    pub failed_tx: usize,
+    /// Count of transactions processed by the network
+    pub confirmed_tx: usize,
+    /// Count of transactions to send
+    pub total_tx: usize,
+}
+
+/// Progress of the speedtest
+#[derive(Debug, Clone, Serialize)]
+pub struct Metrics {
+    /// Name of the current step
+    pub state: &'static str,
+    /// Count of transactions sent
+    pub sent_tx: usize,
+    /// Count of transactions the node failed to receive, or which can't be processed anymore
+    pub failed_tx: usize,
+    /// Count of transactions processed by the network
+    pub confirmed_tx: usize,
+    /// Count of transactions to send
+    pub total_tx: usize,
+    /// Time spent running, in milliseconds
+    pub elapsed_ms: i64,
+    /// Processed transactions per second
+    pub tps: u64,
+    /// Progress of each sender
+    pub senders: Vec<SenderMetrics>,
+}
+
+/// Speedtest state machine
+#[derive(Debug, Clone)]
+pub struct Speedtest {
+    main_user: User,
+    /// Last sequence used by the main account before the speedtest
+    main_sequence: sieve::Sequence,
+    recipient: Contact,
+
+    /// The main account is the only sender
+    single_sender: bool,
+    senders: Vec<Sender>,
+    /// For each sender, if its credit was successfully sent, `None` until known
+    funding: Vec<Option<bool>>,
+    state: State,
+}
+
+impl Speedtest {
+    /// Start a new speedtest sending `total_tx` transactions of one asset to `recipient`.
+    ///
+    /// With more than a single sender, ephemeral accounts are generated and
+    /// funded by the main account, the returned transfers have to be sent for
+    /// the test to go on.
+    pub fn start(
+        (main_user, main_sequence): (User, sieve::Sequence),
+        recipient: Contact,
+        total_tx: usize,
+        senders_count: usize,
+        now: DateTime<Utc>,
+    ) -> (Self, Vec<Transfer>) {
+        if senders_count <= 1 {
+            let senders = vec![Sender::new(main_user.clone(), main_sequence, total_tx)];
+
+            return (
+                Self {
+                    main_user,
+                    main_sequence,
+                    recipient,
+                    single_sender: true,
+                    senders,
+                    funding: Vec::new(),
+                    state: State::Started { started_at: now },
+                },
+                Vec::new(),
+            );
+        }
+
+        let senders_count = senders_count.min(total_tx).max(1);
+        let senders = (0..senders_count)
+            .map(|index| {
+                let user = User::new(
+                    names::Generator::default().next().unwrap(), // can't fail
+                    sign::KeyPair::random(),
+                );
+                let share =
+                    total_tx / senders_count + usize::from(index < total_tx % senders_count);
+
+                Sender::new(user, 0, share)
+            })
+            .collect::<Vec<_>>();
+
+        let funding = (main_sequence + 1..)
+            .zip(senders.iter().enumerate())
+            .map(|(sequence, (index, sender))| Transfer {
+                sender: index,
+                user: main_user.clone(),
+                sequence,
+                recipient: sender.user.clone().to_thin(),
+                amount: sender.total_tx as u64,
+            })
+            .collect();
+
+        (
+            Self {
+                main_user,
+                main_sequence,
+                recipient,
+                single_sender: false,
+                funding: vec![None; senders.len()],
+                senders,
+                state: State::Funding {
+                    funded_sequence: main_sequence + senders_count as sieve::Sequence,
+                },
+            },
+            funding,
+        )
+    }
+
+    /// Current step
+    pub fn state(&self) -> State {
+        self.state
+    }
+
+    /// Accounts flooding the network
+    pub fn senders(&self) -> &[Sender] {
+        &self.senders
+    }
+
+    /// Account which started the speedtest
+    pub fn main_user(&self) -> &User {
+        &self.main_user
+    }
+
+    /// Every transaction was processed
+    pub fn is_done(&self) -> bool {
+        matches!(self.state, State::Done { .. })
+    }
+
+    /// Some transactions still have to be sent
+    pub fn has_remaining(&self) -> bool {
+        self.senders
+            .iter()
+            .any(|sender| !sender.stalled && sender.scheduled_tx < sender.total_tx)
+    }
+
+    /// Time spent running
+    pub fn elapsed(&self, now: DateTime<Utc>) -> Duration {
+        match self.state {
+            State::Funding { .. } | State::CancellingFunding => Duration::zero(),
+            State::Started { started_at } => now - started_at,
+            State::Paused { elapsed }
+            | State::Cancelling { elapsed }
+            | State::Done { elapsed, .. } => elapsed,
+        }
+    }
+
+    /// Mark the credit of the given sender as sent, successfully or not.
+    ///
+    /// A failed send cancels the speedtest, as the senders can't all be credited anymore.
+    pub fn funding_sent(&mut self, sender: usize, success: bool) {
+        if let Some(funding) = self.funding.get_mut(sender) {
+            *funding = Some(success);
+        }
+
+        if !success && matches!(self.state, State::Funding { .. }) {
+            self.state = State::CancellingFunding;
+        }
+    }
+
+    /// Last sequence of the main account once the sent credits are processed,
+    /// `None` while some are still being sent
+    fn funding_settled_sequence(&self) -> Option<sieve::Sequence> {
+        if self.funding.contains(&None) {
+            return None;
+        }
+
+        // the network can't process the sequences following a failed one
+        let processed = self
+            .funding
+            .iter()
+            .take_while(|funding| **funding == Some(true))
+            .count();
+
+        Some(self.main_sequence + processed as sieve::Sequence)
+    }
+
+    /// Update with the last sequence of the main account.
+    ///
+    /// Returns the sequence to store for the main account when every sender
+    /// is credited, or once the sent credits are processed when cancelled.
+    pub fn got_main_sequence(
+        &mut self,
+        sequence: sieve::Sequence,
+        now: DateTime<Utc>,
+    ) -> Option<sieve::Sequence> {
+        match self.state {
+            State::Funding { funded_sequence } if sequence >= funded_sequence => {
+                self.state = State::Started { started_at: now };
+                Some(sequence)
+            }
+            State::CancellingFunding
+                if self
+                    .funding_settled_sequence()
+                    .map_or(false, |settled| sequence >= settled) =>
+            {
+                self.state = State::Done {
+                    elapsed: Duration::zero(),
+                    cancelled: true,
+                };
+                Some(sequence)
+            }
+            _ => None,
+        }
+    }
+
+    /// Take the next transactions to send, at most `max` per sender
+    pub fn next_transfers(&mut self, max: usize) -> Vec<Transfer> {
+        if !matches!(self.state, State::Started { .. }) {
+            return Vec::new();
+        }
+
+        let recipient = &self.recipient;
+        self.senders
+            .iter_mut()
+            .enumerate()
+            .filter(|(_, sender)| !sender.stalled)
+            .flat_map(|(index, sender)| {
+                let transfers_count = (sender.total_tx - sender.scheduled_tx).min(max);
+                let base_sequence =
+                    sender.base_sequence + sender.scheduled_tx as sieve::Sequence + 1;
+                sender.scheduled_tx += transfers_count;
+
+                let user = sender.user.clone();
+                (0..transfers_count).map(move |i| Transfer {
+                    sender: index,
+                    user: user.clone(),
+                    sequence: base_sequence + i as sieve::Sequence,
+                    recipient: recipient.clone(),
+                    amount: 1,
+                })
+            })
+            .collect()
+    }
+
+    /// Mark a transfer of the given sender as sent, successfully or not.
+    ///
+    /// A failed send stops the sender, as the network can't process its following sequences.
+    pub fn transfer_sent(&mut self, sender: usize, success: bool) {
+        if let Some(sender) = self.senders.get_mut(sender) {
+            sender.sent_tx += 1;
+            if !success {
+                sender.failed_tx += 1;
+                sender.stalled = true;
+            }
+        }
+    }
+
+    /// Update with the last sequence of the given sender.
+    ///
+    /// Returns the sequence to store for the main account when it was used to send.
+    pub fn got_sequence(
+        &mut self,
+        sender: usize,
+        sequence: sieve::Sequence,
+        now: DateTime<Utc>,
+    ) -> Option<sieve::Sequence> {
+        let sender = self.senders.get_mut(sender)?;
+        sender.confirmed_tx =
+            (sequence.saturating_sub(sender.base_sequence) as usize).min(sender.total_tx);
+
+        let all_finished = self.senders.iter().all(Sender::is_finished);
+
+        let (elapsed, cancelled) = match self.state {
+            State::Started { started_at } if all_finished => (now - started_at, false),
+            State::Paused { elapsed } if all_finished => (elapsed, false),
+            State::Cancelling { elapsed } if self.senders.iter().all(Sender::is_settled) => {
+                (elapsed, true)
+            }
+            _ => return None,
+        };
+        self.state = State::Done { elapsed, cancelled };
+
+        // only the main account is reused afterwards
+        self.single_sender.then(|| sequence)
+    }
+
+    /// Stop sending until resumed
+    pub fn pause(&mut self, now: DateTime<Utc>) {
+        if let State::Started { started_at } = self.state {
+            self.state = State::Paused {
+                elapsed: now - started_at,
+            };
+        }
+    }
+
+    /// Go on sending, returns if it was paused
+    pub fn resume(&mut self, now: DateTime<Utc>) -> bool {
+        if let State::Paused { elapsed } = self.state {
+            self.state = State::Started {
+                started_at: now - elapsed,
+            };
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Stop sending and wait for the transactions in flight.
+    ///
+    /// While funding, it waits for the credits already sent, the senders keeping them.
+    pub fn cancel(&mut self, now: DateTime<Utc>) {
+        let elapsed = match self.state {
+            State::Funding { .. } => {
+                self.state = State::CancellingFunding;
+                return;
+            }
+            State::Started { started_at } => now - started_at,
+            State::Paused { elapsed } => elapsed,
+            _ => return,
+        };
+
+        // only wait for what was actually sent
+        self.senders
+            .iter_mut()
+            .for_each(|sender| sender.total_tx = sender.scheduled_tx);
+
+        self.state = State::Cancelling { elapsed };
+    }
+
+    /// Summarize the progress
+    pub fn metrics(&self, now: DateTime<Utc>) -> Metrics {
+        let elapsed = self.elapsed(now);
+
+        let senders = self
+            .senders
+            .iter()
+            .map(|sender| SenderMetrics {
+                name: sender.user.name.clone(),
+                // a processed transaction was sent, even if the reply is still on its way
+                sent_tx: sender.sent_tx.max(sender.confirmed_tx),
+                failed_tx: sender.failed_tx(),
+                confirmed_tx: sender.confirmed_tx,
+                total_tx: sender.total_tx,
+            })
+            .collect::<Vec<_>>();
+
+        let confirmed_tx = senders.iter().map(|sender| sender.confirmed_tx).sum();
+        let tps = (confirmed_tx as u64 * 1000)
+            .checked_div(elapsed.num_milliseconds() as u64)
+            .unwrap_or(0);
+
+        Metrics {
+            state: match self.state {
+                State::Funding { .. } => "funding",
+                State::Started { .. } => "started",
+                State::Paused { .. } => "paused",
+                State::CancellingFunding | State::Cancelling { .. } => "cancelling",
+                State::Done {
+                    cancelled: false, ..
+                } => "done",
+                State::Done {
+                    cancelled: true, ..
+                } => "cancelled",
+            },
+            sent_tx: senders.iter().map(|sender| sender.sent_tx).sum(),
+            failed_tx: senders.iter().map(|sender| sender.failed_tx).sum(),
+            confirmed_tx,
+            total_tx: senders.iter().map(|sender| sender.total_tx).sum(),
+            elapsed_ms: elapsed.num_milliseconds(),
+            tps,
+            senders,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use at2_ns::User;
+    use chrono::{Duration, Utc};
+    use drop::crypto::sign::KeyPair;
+
+    use super::{Speedtest, State};
+
+    fn main_user() -> (User, sieve::Sequence) {
+        (User::new("main".to_owned(), KeyPair::random()), 10)
+    }
+
+    fn recipient() -> at2_ns::Contact {
+        User::new("recipient".to_owned(), KeyPair::random()).to_thin()
+    }
+
+    #[test]
+    fn single_sender_uses_main_sequences() {
+        let now = Utc::now();
+        let (mut speedtest, funding) = Speedtest::start(main_user(), recipient(), 3, 1, now);
+        assert!(funding.is_empty());
+
+        let transfers = speedtest.next_transfers(2);
+        assert_eq!(
+            transfers.iter().map(|t| t.sequence).collect::<Vec<_>>(),
+            vec![11, 12],
+        );
+        assert!(speedtest.has_remaining());
+
+        let transfers = speedtest.next_transfers(2);
+        assert_eq!(
+            transfers.iter().map(|t| t.sequence).collect::<Vec<_>>(),
+            vec![13],
+        );
+        assert!(!speedtest.has_remaining());
+
+        assert_eq!(speedtest.got_sequence(0, 12, now), None);
+        assert_eq!(
+            speedtest.got_sequence(0, 13, now + Duration::seconds(1)),
+            Some(13),
+        );
+        assert!(speedtest.is_done());
+        assert_eq!(speedtest.metrics(now).tps, 3);
+    }
+
+    #[test]
+    fn multiple_senders_are_funded_first() {
+        let now = Utc::now();
+        let (mut speedtest, funding) = Speedtest::start(main_user(), recipient(), 10, 3, now);
+
+        assert_eq!(
+            funding.iter().map(|t| t.sequence).collect::<Vec<_>>(),
+            vec![11, 12, 13],
+        );
+        assert_eq!(
+            funding.iter().map(|t| t.amount).sum::<u64>(),
+            10,
+            "funding covers every transaction",
+        );
+
+        assert!(speedtest.next_transfers(100).is_empty());
+        assert_eq!(speedtest.got_main_sequence(12, now), None);
+        assert_eq!(speedtest.got_main_sequence(13, now), Some(13));
+
+        let transfers = speedtest.next_transfers(100);
+        assert_eq!(transfers.len(), 10);
+        assert!(transfers.iter().all(|t| t.sequence >= 1));
+
+        for (index, sender) in speedtest.senders().to_vec().iter().enumerate() {
+            assert_eq!(
+                speedtest.got_sequence(index, sender.total_tx() as sieve::Sequence, now),
+                None,
+                "ephemeral sequences are not reused",
+            );
+        }
+        assert!(speedtest.is_done());
+    }
+
+    #[test]
+    fn paused_does_not_send() {
+        let now = Utc::now();
+        let (mut speedtest, _) = Speedtest::start(main_user(), recipient(), 10, 1, now);
+
+        speedtest.pause(now + Duration::seconds(2));
+        assert!(speedtest.next_transfers(5).is_empty());
+
+        assert!(speedtest.resume(now + Duration::seconds(5)));
+        assert_eq!(
+            speedtest.elapsed(now + Duration::seconds(6)),
+            Duration::seconds(3),
+        );
+        assert_eq!(speedtest.next_transfers(5).len(), 5);
+    }
+
+    #[test]
+    fn cancel_waits_for_in_flight_transactions() {
+        let now = Utc::now();
+        let (mut speedtest, _) = Speedtest::start(main_user(), recipient(), 10, 1, now);
+
+        speedtest.next_transfers(4);
+        speedtest.transfer_sent(0, true);
+        speedtest.cancel(now);
+        assert!(matches!(speedtest.state(), State::Cancelling { .. }));
+        assert!(speedtest.next_transfers(4).is_empty());
+
+        assert_eq!(speedtest.got_sequence(0, 11, now), None);
+
+        (0..2).for_each(|_| speedtest.transfer_sent(0, true));
+        speedtest.transfer_sent(0, false);
+        assert_eq!(speedtest.got_sequence(0, 13, now), Some(13));
+        assert!(matches!(
+            speedtest.state(),
+            State::Done {
+                cancelled: true,
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn cancel_while_funding_waits_for_the_main_sequence() {
+        let now = Utc::now();
+        let (mut speedtest, funding) = Speedtest::start(main_user(), recipient(), 10, 3, now);
+
+        speedtest.cancel(now);
+        assert_eq!(speedtest.state(), State::CancellingFunding);
+        assert!(speedtest.next_transfers(4).is_empty());
+
+        speedtest.funding_sent(funding[0].sender, true);
+        speedtest.funding_sent(funding[2].sender, true);
+        assert_eq!(speedtest.got_main_sequence(11, now), None);
+
+        speedtest.funding_sent(funding[1].sender, false);
+        assert_eq!(speedtest.got_main_sequence(10, now), None);
+        assert_eq!(speedtest.got_main_sequence(11, now), Some(11));
+        assert!(matches!(
+            speedtest.state(),
+            State::Done {
+                cancelled: true,
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn failed_funding_cancels() {
+        let now = Utc::now();
+        let (mut speedtest, funding) = Speedtest::start(main_user(), recipient(), 10, 2, now);
+
+        speedtest.funding_sent(funding[0].sender, false);
+        assert_eq!(speedtest.state(), State::CancellingFunding);
+
+        speedtest.funding_sent(funding[1].sender, true);
+        assert_eq!(speedtest.got_main_sequence(10, now), Some(10));
+        assert!(speedtest.is_done());
+    }
+
+    #[test]
+    fn failed_send_stops_the_sender() {
+        let now = Utc::now();
+        let (mut speedtest, _) = Speedtest::start(main_user(), recipient(), 10, 1, now);
+
+        speedtest.next_transfers(4);
+        speedtest.transfer_sent(0, true);
+        speedtest.transfer_sent(0, false);
+        assert!(!speedtest.has_remaining());
+        assert!(speedtest.next_transfers(4).is_empty());
+
+        assert_eq!(speedtest.got_sequence(0, 11, now), None);
+        (0..2).for_each(|_| speedtest.transfer_sent(0, true));
+        assert_eq!(speedtest.got_sequence(0, 11, now), Some(11));
+        assert!(matches!(
+            speedtest.state(),
+            State::Done {
+                cancelled: false,
+                ..
+            }
+        ));
+
+        let metrics = speedtest.metrics(now);
+        assert_eq!(metrics.sent_tx, 4);
+        assert_eq!(metrics.confirmed_tx, 1);
+        assert_eq!(metrics.failed_tx, 9);
+    }
+
+    #[test]
+    fn failed_send_does_not_block_cancel() {
+        let now = Utc::now();
+        let (mut speedtest, _) = Speedtest::start(main_user(), recipient(), 10, 2, now);
+        speedtest.got_main_sequence(12, now);
+
+        speedtest.next_transfers(3);
+        speedtest.transfer_sent(0, false);
+        (0..2).for_each(|_| speedtest.transfer_sent(0, true));
+        (0..3).for_each(|_| speedtest.transfer_sent(1, true));
+        speedtest.cancel(now);
+
+        assert_eq!(speedtest.got_sequence(1, 2, now), None);
+        assert!(matches!(speedtest.state(), State::Cancelling { .. }));
+        assert_eq!(speedtest.got_sequence(1, 3, now), None);
+        assert!(matches!(
+            speedtest.state(),
+            State::Done {
+                cancelled: true,
+                ..
+            }
+        ));
+    }
+}
@@ -0,0 +1,45 @@
+use at2_ns::{
+    client::{self, WebClient},
+    History,
+};
+use drop::crypto::sign;
+use wasm_bindgen_futures::spawn_local;
+use yew::worker::*;
+
+use crate::config::Config;
+
+/// Get the names used by a user
+pub struct GetHistory {
+    link: AgentLink<Self>,
+    client: WebClient,
+}
+
+impl Agent for GetHistory {
+    type Reach = Context<Self>;
+    type Message = (HandlerId, sign::PublicKey, Result<History, client::Error>);
+    type Input = sign::PublicKey;
+    type Output = (sign::PublicKey, Result<History, client::Error>);
+
+    fn create(link: AgentLink<Self>) -> Self {
+        let conf = Config::parse();
+
+        Self {
+            link,
+            client: WebClient::new(conf.name_service()),
+        }
+    }
+
+    fn update(&mut self, (id, public_key, ret): Self::Message) {
+        self.link.respond(id, (public_key, ret));
+    }
+
+    fn handle_input(&mut self, public_key: Self::Input, id: HandlerId) {
+        let mut client = self.client.clone();
+        let callback = self.link.callback(|ret| ret);
+
+        spawn_local(async move {
+            let ret = client.get_history(&public_key).await;
+            callback.emit((id, public_key, ret));
+        });
+    }
+}
@@ -1,6 +1,6 @@
 use std::collections::HashSet;
 
-use at2_ns::{client::Client, Contact};
+use at2_ns::{client::WebClient, Contact};
 use gloo_timers::callback::Interval;
 use wasm_bindgen_futures::spawn_local;
 use yew::{services::ConsoleService, worker::*};
@@ -27,7 +27,7 @@ impl Agent for GetUsers {
 
     fn create(link: AgentLink<Self>) -> Self {
         let conf = Config::parse();
-        let client = Client::new(conf.name_service());
+        let client = WebClient::new(conf.name_service());
         let update_users = link.callback(|users| users);
 
         Self {
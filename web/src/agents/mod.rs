@@ -1,7 +1,9 @@
 //! Yew agents handling asynchronous calls to services
 
 mod node;
+mod get_history;
 mod get_users;
 
 pub use node::*;
+pub use get_history::GetHistory;
 pub use get_users::GetUsers;
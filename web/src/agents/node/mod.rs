@@ -2,8 +2,10 @@ mod get_balance;
 mod get_last_sequence;
 mod get_latest_transactions;
 mod send_asset;
+mod transaction_history;
 
 pub use get_balance::GetBalance;
 pub use get_last_sequence::GetLastSequence;
 pub use get_latest_transactions::GetLatestTransactions;
 pub use send_asset::SendAsset;
+pub use transaction_history::TransactionHistory;
@@ -0,0 +1,108 @@
+use std::collections::{HashSet, VecDeque};
+
+use at2_node::FullTransaction;
+use drop::crypto::sign;
+use yew::worker::*;
+
+use super::GetLatestTransactions;
+
+/// Older transactions are forgotten past this count
+const MAX_TRANSACTIONS: usize = 1_000;
+
+/// What the transactions are known by, as a sender never reuses a sequence
+type TransactionId = (sign::PublicKey, sieve::Sequence);
+
+fn id(tx: &FullTransaction) -> TransactionId {
+    (tx.sender, tx.sender_sequence)
+}
+
+/// Accumulate the latest transactions, newest first, since the page was opened
+pub struct TransactionHistory {
+    link: AgentLink<Self>,
+
+    #[allow(dead_code)] // never dropped
+    get_latest_transactions_agent: Box<dyn Bridge<GetLatestTransactions>>,
+    transactions: VecDeque<FullTransaction>,
+    known: HashSet<TransactionId>,
+
+    subscribers: HashSet<HandlerId>,
+}
+
+impl TransactionHistory {
+    /// Add the new transactions and update the state of the known ones, returning if any changed
+    fn merge(&mut self, mut latest: Vec<FullTransaction>) -> bool {
+        let mut changed = false;
+
+        // oldest first, so that the newest ends up in front
+        latest.sort_by_key(|tx| tx.timestamp);
+
+        for tx in latest {
+            if self.known.insert(id(&tx)) {
+                self.transactions.push_front(tx);
+                changed = true;
+            } else if let Some(known) = self
+                .transactions
+                .iter_mut()
+                .find(|known| id(known) == id(&tx))
+            {
+                if *known != tx {
+                    *known = tx;
+                    changed = true;
+                }
+            }
+        }
+
+        while self.transactions.len() > MAX_TRANSACTIONS {
+            if let Some(dropped) = self.transactions.pop_back() {
+                self.known.remove(&id(&dropped));
+            }
+        }
+
+        changed
+    }
+
+    fn send(&self, id: HandlerId) {
+        self.link
+            .respond(id, self.transactions.iter().cloned().collect());
+    }
+}
+
+impl Agent for TransactionHistory {
+    type Reach = Context<Self>;
+    type Message = Vec<FullTransaction>;
+    type Input = ();
+    type Output = Vec<FullTransaction>;
+
+    fn create(link: AgentLink<Self>) -> Self {
+        let get_latest_transactions_agent = GetLatestTransactions::bridge(link.callback(|txs| txs));
+
+        Self {
+            link,
+
+            get_latest_transactions_agent,
+            transactions: VecDeque::new(),
+            known: HashSet::new(),
+
+            subscribers: HashSet::new(),
+        }
+    }
+
+    fn update(&mut self, latest: Self::Message) {
+        if self.merge(latest) {
+            self.subscribers.iter().for_each(|id| self.send(*id));
+        }
+    }
+
+    fn handle_input(&mut self, _: Self::Input, _: HandlerId) {
+        panic!("do not support input, only subscribing");
+    }
+
+    fn connected(&mut self, id: HandlerId) {
+        self.subscribers.insert(id);
+        self.send(id);
+    }
+
+    fn disconnected(&mut self, id: HandlerId) {
+        self.subscribers.remove(&id);
+    }
+}
@@ -1,13 +1,49 @@
 // TODO should be replace by parsing a config file
 
 use http::Uri;
+use yew::services::ConsoleService;
 
 const NAME_SERVICE_URI: &str = "https://factory.c4dt.org/incubator/at2/demo/ns";
 const NETWORK_URI: &str = "https://factory.c4dt.org/incubator/at2/demo/leader";
+/// Nodes shown in the topology, as `name=uri` separated by commas, given when building
+const NODES: &str = match option_env!("AT2_NODES") {
+    Some(nodes) => nodes,
+    None => "C4DT=https://factory.c4dt.org/incubator/at2/demo/leader",
+};
+
+/// Node of the network, as probed from the browser
+#[derive(Clone, PartialEq)]
+pub struct Node {
+    pub name: String,
+    pub uri: Uri,
+}
+
+/// Skip malformed nodes, logging why, as panicking would blank the page
+fn parse_node(node: &str) -> Option<Node> {
+    let (name, uri) = match node.split_once('=') {
+        Some(split) => split,
+        None => {
+            ConsoleService::error(&format!("node not as name=uri: {}", node));
+            return None;
+        }
+    };
+
+    match uri.trim().parse() {
+        Ok(uri) => Some(Node {
+            name: name.trim().to_owned(),
+            uri,
+        }),
+        Err(err) => {
+            ConsoleService::error(&format!("invalid uri for node {}: {}", name.trim(), err));
+            None
+        }
+    }
+}
 
 pub struct Config {
     name_service: Uri,
     network: Uri,
+    nodes: Vec<Node>,
 }
 
 impl Config {
@@ -15,6 +51,7 @@ impl Config {
         Self {
             name_service: NAME_SERVICE_URI.parse().unwrap(),
             network: NETWORK_URI.parse().unwrap(),
+            nodes: NODES.split(',').filter_map(parse_node).collect(),
         }
     }
 
@@ -25,4 +62,8 @@ impl Config {
     pub fn network(&self) -> &Uri {
         &self.network
     }
+
+    pub fn nodes(&self) -> &[Node] {
+        &self.nodes
+    }
 }
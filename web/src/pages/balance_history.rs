@@ -0,0 +1,164 @@
+use std::collections::{HashSet, VecDeque};
+
+use at2_node::{FullTransaction, TransactionState};
+use chrono::{DateTime, Duration, Utc};
+use drop::crypto::sign;
+use yew::prelude::*;
+
+/// Older samples are dropped past this count
+const MAX_SAMPLES: usize = 500;
+
+const WIDTH: f64 = 600.0;
+const HEIGHT: f64 = 150.0;
+
+/// Transaction of the user, as seen in the feed
+struct Transfer {
+    id: TransferId,
+    at: DateTime<Utc>,
+    amount: u64,
+    incoming: bool,
+}
+
+/// What the transactions are known by, as a sender never reuses a sequence
+type TransferId = (sign::PublicKey, sieve::Sequence);
+
+/// Evolution of the balance of a user, since the page was opened
+#[derive(Default)]
+pub struct BalanceHistory {
+    /// Only the changes of balance, oldest first
+    samples: VecDeque<(DateTime<Utc>, u64)>,
+    transfers: VecDeque<Transfer>,
+    seen: HashSet<TransferId>,
+}
+
+impl BalanceHistory {
+    /// Record a polled balance, if it changed
+    pub fn record_balance(&mut self, at: DateTime<Utc>, balance: u64) {
+        if self.samples.back().map(|(_, last)| *last) == Some(balance) {
+            return;
+        }
+
+        self.samples.push_back((at, balance));
+        if self.samples.len() > MAX_SAMPLES {
+            self.samples.pop_front();
+        }
+    }
+
+    /// Record the successful transactions from or to the user, ignoring the already seen ones
+    pub fn record_transactions<'a>(
+        &mut self,
+        user: &sign::PublicKey,
+        transactions: impl IntoIterator<Item = &'a FullTransaction>,
+    ) {
+        for tx in transactions {
+            let id = (tx.sender, tx.sender_sequence);
+            if !matches!(tx.state, TransactionState::Success)
+                || (tx.sender != *user && tx.recipient != *user)
+                || !self.seen.insert(id)
+            {
+                continue;
+            }
+
+            self.transfers.push_back(Transfer {
+                id,
+                at: tx.timestamp,
+                amount: tx.amount,
+                incoming: tx.recipient == *user,
+            });
+            if self.transfers.len() > MAX_SAMPLES {
+                if let Some(dropped) = self.transfers.pop_front() {
+                    self.seen.remove(&dropped.id);
+                }
+            }
+        }
+    }
+
+    fn total(&self, incoming: bool) -> (u64, usize) {
+        self.transfers
+            .iter()
+            .filter(|transfer| transfer.incoming == incoming)
+            .fold((0, 0), |(amount, count), transfer| {
+                (amount + transfer.amount, count + 1)
+            })
+    }
+
+    /// Step chart of the balance, with the transfers marked on it
+    pub fn view(&self, now: DateTime<Utc>) -> Html {
+        let start = match self.samples.front() {
+            Some((start, _)) => *start,
+            None => return html! {},
+        };
+        let max = self
+            .samples
+            .iter()
+            .map(|(_, balance)| *balance)
+            .max()
+            .unwrap_or_default()
+            .max(1);
+        let span = (now - start).max(Duration::seconds(1));
+
+        let x = |at: DateTime<Utc>| {
+            ((at - start).num_milliseconds().max(0) as f64 / span.num_milliseconds() as f64) * WIDTH
+        };
+        let y = |balance: u64| HEIGHT - (balance as f64 / max as f64) * HEIGHT;
+
+        let mut points = Vec::with_capacity(self.samples.len() * 2 + 1);
+        let mut previous = None;
+        for (at, balance) in &self.samples {
+            if let Some(previous) = previous {
+                points.push(format!("{:.1},{:.1}", x(*at), y(previous)));
+            }
+            points.push(format!("{:.1},{:.1}", x(*at), y(*balance)));
+            previous = Some(*balance);
+        }
+        if let Some(last) = previous {
+            points.push(format!("{:.1},{:.1}", WIDTH, y(last)));
+        }
+
+        let (received, received_count) = self.total(true);
+        let (sent, sent_count) = self.total(false);
+
+        html! { <>
+            <svg
+                viewBox=format!("0 0 {} {}", WIDTH, HEIGHT)
+                preserveAspectRatio="none"
+                style="width: 100%; height: 10em; border-bottom: 1px solid; overflow: visible;"
+            >
+                <polyline
+                    points=points.join(" ")
+                    fill="none"
+                    stroke="currentColor"
+                    stroke-width="2"
+                    vector-effect="non-scaling-stroke"
+                />
+                { for self.transfers.iter().filter(|transfer| transfer.at >= start).map(|transfer| html! {
+                    <line
+                        x1=format!("{:.1}", x(transfer.at))
+                        x2=format!("{:.1}", x(transfer.at))
+                        y1="0"
+                        y2=HEIGHT.to_string()
+                        stroke={ if transfer.incoming { "green" } else { "violet" } }
+                        stroke-width="1"
+                        vector-effect="non-scaling-stroke"
+                    >
+                        <title>{ format!(
+                            "{}{} ₳",
+                            if transfer.incoming { "+" } else { "-" },
+                            transfer.amount,
+                        ) }</title>
+                    </line>
+                }) }
+            </svg>
+
+            <p>
+                <span style="color: green">
+                    { format!("received {} ₳ in {} transactions", received, received_count) }
+                </span>
+                { ", " }
+                <span style="color: violet">
+                    { format!("sent {} ₳ in {} transactions", sent, sent_count) }
+                </span>
+            </p>
+        </> }
+    }
+}
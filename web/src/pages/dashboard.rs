@@ -0,0 +1,253 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use at2_node::{FullTransaction, TransactionState};
+use at2_ns::Contact;
+use chrono::{DateTime, Duration, Utc};
+use drop::crypto::sign;
+use gloo_timers::callback::Interval;
+use yew::{prelude::*, worker::Agent};
+
+use super::identity::Identity;
+use crate::agents;
+
+/// Seconds over which the throughput is averaged
+const THROUGHPUT_WINDOW: i64 = 10;
+/// Throughput samples kept, one per second
+const MAX_SAMPLES: usize = 120;
+/// Seconds since its last transaction for an account to be active
+const ACTIVE_WINDOW: i64 = 5 * 60;
+const TOP_COUNT: usize = 5;
+
+const WIDTH: f64 = 600.0;
+const HEIGHT: f64 = 100.0;
+
+pub enum Message {
+    GotTransactions(<agents::TransactionHistory as Agent>::Output),
+    GotUsers(<agents::GetUsers as Agent>::Output),
+    Tick,
+}
+
+/// Statistics of the network, computed from the transactions seen since the page was opened
+pub struct Dashboard {
+    #[allow(dead_code)] // never dropped
+    transaction_history_agent: Box<dyn Bridge<agents::TransactionHistory>>,
+    transactions: Vec<FullTransaction>,
+    #[allow(dead_code)] // never dropped
+    get_users_agent: Box<dyn Bridge<agents::GetUsers>>,
+    pubkey_to_contact: HashMap<sign::PublicKey, Contact>,
+
+    /// Transactions per second, oldest first
+    throughput: VecDeque<f64>,
+    #[allow(dead_code)] // never dropped
+    ticker: Interval,
+}
+
+impl Dashboard {
+    fn since(
+        &self,
+        now: DateTime<Utc>,
+        seconds: i64,
+    ) -> impl Iterator<Item = &FullTransaction> + '_ {
+        let start = now - Duration::seconds(seconds);
+
+        self.transactions
+            .iter()
+            .filter(move |tx| tx.timestamp >= start)
+    }
+
+    /// Most frequent parties, with their number of transactions
+    fn top(&self, party: fn(&FullTransaction) -> sign::PublicKey) -> Vec<(sign::PublicKey, usize)> {
+        let mut counts = HashMap::new();
+        for tx in &self.transactions {
+            *counts.entry(party(tx)).or_insert(0) += 1;
+        }
+
+        let mut counts = counts.into_iter().collect::<Vec<_>>();
+        counts.sort_by(|(_, a), (_, b)| b.cmp(a));
+        counts.truncate(TOP_COUNT);
+
+        counts
+    }
+
+    fn view_top(&self, title: &str, party: fn(&FullTransaction) -> sign::PublicKey) -> Html {
+        html! {
+            <div>
+                <h3>{ title }</h3>
+                <ol style="text-align: left;">
+                    { for self.top(party).into_iter().map(|(public_key, count)| html! {
+                        <li>
+                            <Identity
+                                public_key=public_key
+                                contact=self.pubkey_to_contact.get(&public_key).cloned()
+                            />
+                            { format!(" ({})", count) }
+                        </li>
+                    }) }
+                </ol>
+            </div>
+        }
+    }
+
+    fn view_throughput(&self) -> Html {
+        let max = self.throughput.iter().copied().fold(1.0, f64::max);
+        let step = WIDTH / (MAX_SAMPLES - 1) as f64;
+
+        // aligned to the right, as the samples come in
+        let offset = MAX_SAMPLES - self.throughput.len();
+        let points = self
+            .throughput
+            .iter()
+            .enumerate()
+            .map(|(index, rate)| {
+                format!(
+                    "{:.1},{:.1}",
+                    (offset + index) as f64 * step,
+                    HEIGHT - rate / max * HEIGHT
+                )
+            })
+            .collect::<Vec<_>>();
+
+        html! {
+            <svg
+                viewBox=format!("0 0 {} {}", WIDTH, HEIGHT)
+                preserveAspectRatio="none"
+                style="width: 100%; height: 6em; border-bottom: 1px solid;"
+            >
+                <polyline
+                    points=points.join(" ")
+                    fill="none"
+                    stroke="currentColor"
+                    stroke-width="2"
+                    vector-effect="non-scaling-stroke"
+                />
+            </svg>
+        }
+    }
+}
+
+impl Component for Dashboard {
+    type Properties = ();
+    type Message = Message;
+
+    fn create(_: Self::Properties, link: ComponentLink<Self>) -> Self {
+        let transaction_history_agent =
+            agents::TransactionHistory::bridge(link.callback(Message::GotTransactions));
+        let get_users_agent = agents::GetUsers::bridge(link.callback(Message::GotUsers));
+
+        let tick = link.callback(|_: ()| Message::Tick);
+
+        Self {
+            transaction_history_agent,
+            transactions: Vec::new(),
+            get_users_agent,
+            pubkey_to_contact: HashMap::new(),
+
+            throughput: VecDeque::with_capacity(MAX_SAMPLES),
+            ticker: Interval::new(1000, move || {
+                tick.emit(());
+            }),
+        }
+    }
+
+    fn update(&mut self, message: Message) -> ShouldRender {
+        match message {
+            Message::GotTransactions(transactions) => self.transactions = transactions,
+            Message::GotUsers(users) => {
+                self.pubkey_to_contact = users
+                    .into_iter()
+                    .map(|user| (*user.public_key(), user))
+                    .collect()
+            }
+            Message::Tick => {
+                let now = Utc::now();
+                let rate =
+                    self.since(now, THROUGHPUT_WINDOW).count() as f64 / THROUGHPUT_WINDOW as f64;
+
+                self.throughput.push_back(rate);
+                if self.throughput.len() > MAX_SAMPLES {
+                    self.throughput.pop_front();
+                }
+            }
+        }
+
+        true
+    }
+
+    fn change(&mut self, _: Self::Properties) -> ShouldRender {
+        false
+    }
+
+    fn view(&self) -> Html {
+        let now = Utc::now();
+
+        let count = |state: fn(&TransactionState) -> bool| {
+            self.transactions
+                .iter()
+                .filter(|tx| state(&tx.state))
+                .count()
+        };
+        let pending = count(|state| matches!(state, TransactionState::Pending));
+        let succeeded = count(|state| matches!(state, TransactionState::Success));
+        let failed = count(|state| matches!(state, TransactionState::Failure));
+
+        let active = self
+            .since(now, ACTIVE_WINDOW)
+            .flat_map(|tx| [tx.sender, tx.recipient])
+            .collect::<HashSet<_>>()
+            .len();
+
+        html! { <>
+            <h1> { "Dashboard" } </h1>
+
+            <p> { "
+                Here is how the network is doing, computed from the
+                transactions seen since you opened this page.
+                Start a speedtest to see it move.
+            " } </p>
+
+            <h3>
+                { "Throughput: " }
+                { format!(
+                    "{:.1} tx/s",
+                    self.throughput.back().copied().unwrap_or_default(),
+                ) }
+            </h3>
+            { self.view_throughput() }
+
+            <table style="width: 100%;">
+                <tbody>
+                    <tr>
+                        <td>{ "Succeeded" }</td>
+                        <td>{ succeeded }</td>
+                        <td>{ "Failed" }</td>
+                        <td>{ failed }</td>
+                    </tr>
+                    <tr>
+                        <td>{ "Success ratio" }</td>
+                        <td>{ if succeeded + failed == 0 {
+                            "-".to_owned()
+                        } else {
+                            format!("{:.1} %", succeeded as f64 * 100.0 / (succeeded + failed) as f64)
+                        } }</td>
+                        <td>{ "Pending" }</td>
+                        <td>{ pending }</td>
+                    </tr>
+                    <tr>
+                        <td>{ "Active accounts" }</td>
+                        <td>{ active }</td>
+                        <td>{ "Registered accounts" }</td>
+                        <td>{ self.pubkey_to_contact.len() }</td>
+                    </tr>
+                </tbody>
+            </table>
+
+            <div style=concat!(
+                "display: flex;",
+                "justify-content: space-around;",
+            )>
+                { self.view_top("Top senders", |tx| tx.sender) }
+                { self.view_top("Top recipients", |tx| tx.recipient) }
+            </div>
+        </> }
+    }
+}
@@ -0,0 +1,382 @@
+use std::collections::HashMap;
+
+use at2_node::{FullTransaction, TransactionState};
+use at2_ns::Contact;
+use chrono::Utc;
+use chrono_humanize::HumanTime;
+use drop::crypto::sign;
+use yew::{prelude::*, worker::Agent};
+
+use super::identity::Identity;
+use crate::agents;
+
+/// Only the first rows matching the filters are shown
+const MAX_ROWS: usize = 100;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum StateFilter {
+    All,
+    Pending,
+    Success,
+    Failure,
+}
+
+impl StateFilter {
+    fn parse(value: &str) -> Self {
+        match value {
+            "pending" => Self::Pending,
+            "success" => Self::Success,
+            "failure" => Self::Failure,
+            _ => Self::All,
+        }
+    }
+
+    fn matches(self, state: &TransactionState) -> bool {
+        matches!(
+            (self, state),
+            (Self::All, _)
+                | (Self::Pending, TransactionState::Pending)
+                | (Self::Success, TransactionState::Success)
+                | (Self::Failure, TransactionState::Failure)
+        )
+    }
+}
+
+/// What is shown next to the list
+enum Details {
+    Account(sign::PublicKey),
+    Transaction(Box<FullTransaction>),
+}
+
+pub enum Message {
+    GotTransactions(<agents::TransactionHistory as Agent>::Output),
+    GotUsers(<agents::GetUsers as Agent>::Output),
+
+    FilterSender(String),
+    FilterRecipient(String),
+    FilterState(StateFilter),
+    FilterMinAmount(String),
+    FilterMaxAmount(String),
+
+    ShowAccount(sign::PublicKey),
+    ShowTransaction(Box<FullTransaction>),
+    HideDetails,
+}
+
+/// Every transaction seen on the network since the page was opened
+pub struct Explorer {
+    link: ComponentLink<Self>,
+
+    #[allow(dead_code)] // never dropped
+    transaction_history_agent: Box<dyn Bridge<agents::TransactionHistory>>,
+    transactions: Vec<FullTransaction>,
+    #[allow(dead_code)] // never dropped
+    get_users_agent: Box<dyn Bridge<agents::GetUsers>>,
+    pubkey_to_contact: HashMap<sign::PublicKey, Contact>,
+
+    sender: String,
+    recipient: String,
+    state: StateFilter,
+    min_amount: String,
+    max_amount: String,
+
+    details: Option<Details>,
+}
+
+fn view_state(state: &TransactionState) -> Html {
+    match state {
+        TransactionState::Pending => html! { <span style="color: grey">{ "pending" }</span> },
+        TransactionState::Success => html! { "success" },
+        TransactionState::Failure => html! { <span style="color: violet">{ "failure" }</span> },
+    }
+}
+
+impl Explorer {
+    /// Match on the name or the key, ignoring case
+    fn matches_party(&self, public_key: &sign::PublicKey, filter: &str) -> bool {
+        let filter = filter.trim().to_lowercase();
+
+        filter.is_empty()
+            || public_key.to_string().to_lowercase().contains(&filter)
+            || self
+                .pubkey_to_contact
+                .get(public_key)
+                .map_or(false, |contact| {
+                    contact.name.to_lowercase().contains(&filter)
+                })
+    }
+
+    fn matches(&self, tx: &FullTransaction) -> bool {
+        let min = self.min_amount.parse::<u64>().unwrap_or(u64::MIN);
+        let max = self.max_amount.parse::<u64>().unwrap_or(u64::MAX);
+
+        self.matches_party(&tx.sender, &self.sender)
+            && self.matches_party(&tx.recipient, &self.recipient)
+            && self.state.matches(&tx.state)
+            && (min..=max).contains(&tx.amount)
+    }
+
+    fn view_party(&self, public_key: &sign::PublicKey) -> Html {
+        let public_key = *public_key;
+
+        html! {
+            <span
+                onclick=self.link.callback(move |_| Message::ShowAccount(public_key))
+                style="cursor: pointer;"
+            >
+                <Identity
+                    public_key=public_key
+                    contact=self.pubkey_to_contact.get(&public_key).cloned()
+                />
+            </span>
+        }
+    }
+
+    fn view_table<'a>(&self, transactions: impl Iterator<Item = &'a FullTransaction>) -> Html {
+        let now = Utc::now();
+
+        html! {
+            <table style=concat!(
+                "width: 100%;",
+                "border-collapse: collapse;",
+            )>
+                <thead><tr>
+                    <td>{ "Date" }</td>
+                    <td>{ "State" }</td>
+                    <td>{ "Transaction" }</td>
+                    <td>{ "Amount" }</td>
+                    <td></td>
+                </tr></thead>
+                <tbody>
+                    { for transactions.take(MAX_ROWS).map(|tx| {
+                        let details = Box::new(tx.clone());
+
+                        html! {
+                            <tr style=concat!(
+                                "border-bottom: 1px solid;",
+                                "border-top: 1px solid;",
+                            )>
+                                <td style="padding: 0 2px;">{ HumanTime::from(tx.timestamp - now) }</td>
+                                <td style="padding: 0 2px;">{ view_state(&tx.state) }</td>
+                                <td style="padding: 0 2px;">
+                                    { self.view_party(&tx.sender) }
+                                    { " -> " }
+                                    { self.view_party(&tx.recipient) }
+                                </td>
+                                <td style="padding: 0 2px;">{ tx.amount } { " ₳" }</td>
+                                <td style="padding: 0 2px;">
+                                    <span
+                                        class="material-icons"
+                                        title="Show the transaction"
+                                        onclick=self.link.callback(move |_| {
+                                            Message::ShowTransaction(details.clone())
+                                        })
+                                        style="cursor: pointer; font-size: 1em;"
+                                    >{ "info" }</span>
+                                </td>
+                            </tr>
+                        }
+                    }) }
+                </tbody>
+            </table>
+        }
+    }
+
+    fn view_account(&self, public_key: &sign::PublicKey) -> Html {
+        let involved = self
+            .transactions
+            .iter()
+            .filter(|tx| tx.sender == *public_key || tx.recipient == *public_key)
+            .collect::<Vec<_>>();
+
+        let total = |pick: fn(&FullTransaction) -> sign::PublicKey| {
+            involved
+                .iter()
+                .filter(|tx| matches!(tx.state, TransactionState::Success))
+                .filter(|tx| pick(tx) == *public_key)
+                .map(|tx| tx.amount)
+                .sum::<u64>()
+        };
+        let pending = involved
+            .iter()
+            .filter(|tx| matches!(tx.state, TransactionState::Pending))
+            .count();
+
+        html! { <>
+            <h3>
+                <Identity
+                    public_key=*public_key
+                    contact=self.pubkey_to_contact.get(public_key).cloned()
+                />
+            </h3>
+
+            <p>
+                { format!("{} transactions seen, {} pending. ", involved.len(), pending) }
+                <span style="color: green">
+                    { format!("Received {} ₳", total(|tx| tx.recipient)) }
+                </span>
+                { ", " }
+                <span style="color: violet">
+                    { format!("sent {} ₳", total(|tx| tx.sender)) }
+                </span>
+                { "." }
+            </p>
+
+            { self.view_table(involved.into_iter()) }
+        </> }
+    }
+
+    fn view_transaction(&self, tx: &FullTransaction) -> Html {
+        html! {
+            <dl style="text-align: left;">
+                <dt>{ "Date" }</dt>
+                <dd>
+                    { tx.timestamp.to_rfc3339() }
+                    { " (" } { HumanTime::from(tx.timestamp - Utc::now()) } { ")" }
+                </dd>
+                <dt>{ "State" }</dt>
+                <dd>{ view_state(&tx.state) }</dd>
+                <dt>{ "Sender" }</dt>
+                <dd>{ self.view_party(&tx.sender) }</dd>
+                <dt>{ "Recipient" }</dt>
+                <dd>{ self.view_party(&tx.recipient) }</dd>
+                <dt>{ "Amount" }</dt>
+                <dd>{ tx.amount } { " ₳" }</dd>
+            </dl>
+        }
+    }
+}
+
+impl Component for Explorer {
+    type Properties = ();
+    type Message = Message;
+
+    fn create(_: Self::Properties, link: ComponentLink<Self>) -> Self {
+        let transaction_history_agent =
+            agents::TransactionHistory::bridge(link.callback(Message::GotTransactions));
+        let get_users_agent = agents::GetUsers::bridge(link.callback(Message::GotUsers));
+
+        Self {
+            link,
+
+            transaction_history_agent,
+            transactions: Vec::new(),
+            get_users_agent,
+            pubkey_to_contact: HashMap::new(),
+
+            sender: String::new(),
+            recipient: String::new(),
+            state: StateFilter::All,
+            min_amount: String::new(),
+            max_amount: String::new(),
+
+            details: None,
+        }
+    }
+
+    fn update(&mut self, message: Message) -> ShouldRender {
+        match message {
+            Message::GotTransactions(transactions) => self.transactions = transactions,
+            Message::GotUsers(users) => {
+                self.pubkey_to_contact = users
+                    .into_iter()
+                    .map(|user| (*user.public_key(), user))
+                    .collect()
+            }
+
+            Message::FilterSender(sender) => self.sender = sender,
+            Message::FilterRecipient(recipient) => self.recipient = recipient,
+            Message::FilterState(state) => self.state = state,
+            Message::FilterMinAmount(amount) => self.min_amount = amount,
+            Message::FilterMaxAmount(amount) => self.max_amount = amount,
+
+            Message::ShowAccount(public_key) => self.details = Some(Details::Account(public_key)),
+            Message::ShowTransaction(tx) => self.details = Some(Details::Transaction(tx)),
+            Message::HideDetails => self.details = None,
+        }
+
+        true
+    }
+
+    fn change(&mut self, _: Self::Properties) -> ShouldRender {
+        false
+    }
+
+    fn view(&self) -> Html {
+        let matching = self.transactions.iter().filter(|tx| self.matches(tx));
+
+        html! { <>
+            <h1> { "Explorer" } </h1>
+
+            <p> { "
+                Here are all the transactions seen on the network since you
+                opened this page, the most recent on top.
+                Filter them, or click on an account or on a transaction to see
+                more about it.
+            " } </p>
+
+            { match &self.details {
+                None => html! {},
+                Some(details) => html! { <>
+                    <hr />
+                    <span
+                        class="material-icons"
+                        title="Close"
+                        onclick=self.link.callback(|_| Message::HideDetails)
+                        style="cursor: pointer; float: right;"
+                    >{ "close" }</span>
+                    { match details {
+                        Details::Account(public_key) => self.view_account(public_key),
+                        Details::Transaction(tx) => self.view_transaction(tx),
+                    } }
+                    <hr />
+                </> },
+            } }
+
+            <div style=concat!(
+                "display: flex;",
+                "flex-wrap: wrap;",
+                "justify-content: space-around;",
+                "gap: 0.5em;",
+            )>
+                <input
+                    placeholder="Sender"
+                    value=self.sender.clone()
+                    oninput=self.link.callback(|event: InputData| Message::FilterSender(event.value))
+                />
+                <input
+                    placeholder="Recipient"
+                    value=self.recipient.clone()
+                    oninput=self.link.callback(|event: InputData| Message::FilterRecipient(event.value))
+                />
+                <select
+                    onchange=self.link.callback(|event: ChangeData| Message::FilterState(match event {
+                        ChangeData::Select(select) => StateFilter::parse(&select.value()),
+                        _ => StateFilter::All,
+                    }))
+                >
+                    <option value="all" selected=self.state == StateFilter::All>{ "Any state" }</option>
+                    <option value="pending" selected=self.state == StateFilter::Pending>{ "Pending" }</option>
+                    <option value="success" selected=self.state == StateFilter::Success>{ "Success" }</option>
+                    <option value="failure" selected=self.state == StateFilter::Failure>{ "Failure" }</option>
+                </select>
+                <input
+                    placeholder="Min ₳"
+                    type="number"
+                    min=0
+                    value=self.min_amount.clone()
+                    oninput=self.link.callback(|event: InputData| Message::FilterMinAmount(event.value))
+                />
+                <input
+                    placeholder="Max ₳"
+                    type="number"
+                    min=0
+                    value=self.max_amount.clone()
+                    oninput=self.link.callback(|event: InputData| Message::FilterMaxAmount(event.value))
+                />
+            </div>
+
+            { self.view_table(matching) }
+        </> }
+    }
+}
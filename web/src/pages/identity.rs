@@ -0,0 +1,171 @@
+use at2_ns::Contact;
+use drop::crypto::sign;
+use js_sys::{Function, Promise, Reflect};
+use wasm_bindgen::JsValue;
+use wasm_bindgen_futures::{spawn_local, JsFuture};
+use yew::{prelude::*, services::ConsoleService};
+
+use super::profile::view_contact;
+
+const IDENTICON_SIZE: usize = 5;
+
+#[derive(Properties, Clone, PartialEq)]
+pub struct Properties {
+    /// Key to show
+    pub public_key: sign::PublicKey,
+    /// Registered account of the key, if any
+    #[prop_or_default]
+    pub contact: Option<Contact>,
+}
+
+pub enum Message {
+    Copy,
+    Copied(bool),
+}
+
+/// Who owns a public key: its name if registered, otherwise a short form of it
+pub struct Identity {
+    link: ComponentLink<Self>,
+    props: Properties,
+
+    copied: bool,
+}
+
+/// FNV-1a, stable across builds, unlike the std hasher
+fn fingerprint(public_key: &sign::PublicKey) -> u64 {
+    public_key
+        .to_string()
+        .bytes()
+        .fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+            (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+        })
+}
+
+/// Symmetric grid of cells, colored from the key
+fn view_identicon(public_key: &sign::PublicKey) -> Html {
+    let fingerprint = fingerprint(public_key);
+    let color = format!("#{:06x}", fingerprint >> 40);
+
+    let half = (IDENTICON_SIZE + 1) / 2;
+    let cells = (0..IDENTICON_SIZE).flat_map(|y| (0..IDENTICON_SIZE).map(move |x| (x, y)));
+
+    html! {
+        <svg
+            viewBox=format!("0 0 {0} {0}", IDENTICON_SIZE)
+            width="16"
+            height="16"
+            style="vertical-align: middle; margin-right: 0.25rem;"
+        >
+            { for cells
+                // mirrored, as faces are
+                .filter(|(x, y)| {
+                    let column = (*x).min(IDENTICON_SIZE - 1 - x);
+                    (fingerprint >> (y * half + column)) & 1 == 1
+                })
+                .map(|(x, y)| html! {
+                    <rect
+                        x=x.to_string()
+                        y=y.to_string()
+                        width="1"
+                        height="1"
+                        fill=color.clone()
+                    />
+                }) }
+        </svg>
+    }
+}
+
+/// Start and end of the key, enough to tell them apart
+fn shorten(key: &str) -> String {
+    if key.len() <= 12 {
+        return key.to_owned();
+    }
+
+    format!("{}…{}", &key[..8], &key[key.len() - 4..])
+}
+
+/// Write to the clipboard, as the `web-sys` bindings are still unstable
+async fn copy_to_clipboard(text: String) -> Result<(), JsValue> {
+    let navigator = Reflect::get(&js_sys::global(), &"navigator".into())?;
+    let clipboard = Reflect::get(&navigator, &"clipboard".into())?;
+    let write_text = Function::from(Reflect::get(&clipboard, &"writeText".into())?);
+
+    JsFuture::from(Promise::from(write_text.call1(&clipboard, &text.into())?)).await?;
+
+    Ok(())
+}
+
+impl Component for Identity {
+    type Properties = Properties;
+    type Message = Message;
+
+    fn create(props: Properties, link: ComponentLink<Self>) -> Self {
+        Self {
+            link,
+            props,
+
+            copied: false,
+        }
+    }
+
+    fn update(&mut self, message: Message) -> ShouldRender {
+        match message {
+            Message::Copy => {
+                let key = self.props.public_key.to_string();
+                let copied = self.link.callback(Message::Copied);
+
+                spawn_local(async move {
+                    match copy_to_clipboard(key).await {
+                        Ok(()) => copied.emit(true),
+                        Err(err) => {
+                            ConsoleService::error(&format!("unable to copy key: {:?}", err));
+                            copied.emit(false);
+                        }
+                    }
+                });
+
+                false
+            }
+            Message::Copied(copied) => {
+                self.copied = copied;
+                true
+            }
+        }
+    }
+
+    fn change(&mut self, props: Properties) -> ShouldRender {
+        if props.public_key != self.props.public_key {
+            self.copied = false;
+        }
+
+        let ret = props != self.props;
+        self.props = props;
+        ret
+    }
+
+    fn view(&self) -> Html {
+        if let Some(contact) = &self.props.contact {
+            return view_contact(contact);
+        }
+
+        let key = self.props.public_key.to_string();
+
+        html! {
+            <span title=key.clone() style="white-space: nowrap;">
+                { view_identicon(&self.props.public_key) }
+                <code>{ shorten(&key) }</code>
+                <span
+                    class="material-icons"
+                    title="Copy the full key"
+                    onclick=self.link.callback(|_| Message::Copy)
+                    style=concat!(
+                        "cursor: pointer;",
+                        "font-size: 1em;",
+                        "vertical-align: middle;",
+                        "margin-left: 0.25rem;",
+                    )
+                >{ if self.copied { "done" } else { "content_copy" } }</span>
+            </span>
+        }
+    }
+}
@@ -1,8 +1,14 @@
+mod balance_history;
+mod dashboard;
+mod explorer;
+mod identity;
 mod new_account;
+mod profile;
 mod select_user;
 mod speedtest;
 mod style;
 mod summary;
+mod topology;
 mod welcome;
 mod your_account;
 
@@ -10,7 +16,9 @@ use std::cmp::min;
 
 use at2_node::client;
 use at2_ns::User;
+use dashboard::Dashboard;
 use drop::crypto::sign;
+use explorer::Explorer;
 use material_yew::MatButton;
 use new_account::NewAccount;
 use speedtest::Speedtest;
@@ -35,7 +43,7 @@ pub enum Message {
     SequenceBumped(sieve::Sequence),
 }
 
-const PAGE_COUNT: usize = 5;
+const PAGE_COUNT: usize = 7;
 const STORAGE_KEY: &str = "at2-user";
 
 /// Component showing the pages
@@ -215,6 +223,12 @@ impl Component for Pages {
                 />
             </div>
             <div class=classes!("page") hidden=self.index != 4>
+                <Explorer/>
+            </div>
+            <div class=classes!("page") hidden=self.index != 5>
+                <Dashboard/>
+            </div>
+            <div class=classes!("page") hidden=self.index != 6>
                 <Summary/>
             </div>
 
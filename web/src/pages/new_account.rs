@@ -1,11 +1,12 @@
 use at2_ns::{
-    client::{self, Client},
+    client::{self, WebClient},
     User,
 };
 use material_yew::{MatButton, MatTextField};
 use wasm_bindgen_futures::spawn_local;
 use yew::prelude::*;
 
+use super::topology::Topology;
 use crate::config::Config;
 
 #[derive(Properties, Clone, PartialEq)]
@@ -35,7 +36,7 @@ pub struct NewAccount {
     link: ComponentLink<Self>,
     properties: Properties,
 
-    client: Client,
+    client: WebClient,
 
     create_user: CreateUser,
 }
@@ -50,7 +51,7 @@ impl Component for NewAccount {
         Self {
             link,
             properties,
-            client: Client::new(conf.name_service()),
+            client: WebClient::new(conf.name_service()),
             create_user: CreateUser::Ready,
         }
     }
@@ -160,56 +161,22 @@ impl Component for NewAccount {
                     } </span>
                 </div>
 
-                { if let CreateUser::Failed(err) = &self.create_user {
-                    html! { <p style="color: red"> { format!("error while creating user: {}", err) } </p> }
-                } else { html! {} } }
+                { match &self.create_user {
+                    CreateUser::Failed(client::Error::NameTaken) => html! { <p style="color: red"> {
+                        "This username is already taken, please choose another one."
+                    } </p> },
+                    CreateUser::Failed(err) => html! { <p style="color: red"> {
+                        format!("error while creating user: {}", err)
+                    } </p> },
+                    _ => html! {},
+                } }
             </div>
 
             <hr />
 
             <h2> { "Network" } </h2>
 
-            <style> { "
-                .boxes {
-                    width: 20em; height: 10em;
-                    margin-left: 10em;
-                    z-index: -1;
-
-                    border: dotted var(--light-grey);
-                }
-
-                .boxes > * {
-                    position: relative;
-                    margin: 0px;
-
-                    background-color: white;
-                    border: solid;
-                    padding: 1em 2em;
-                    width: 2em;
-                }
-                @media (prefers-color-scheme: dark) {
-                    .boxes > * {
-                      background-color: var(--dark-grey);
-                    }
-                }
-
-                .boxes > :nth-child(1) {
-                    top: -2em; left: -4em;
-                }
-                .boxes > :nth-child(2) {
-                    top: -6em; left: 20em;
-                }
-                .boxes > :nth-child(3) {
-                    top: 2em; left: 8em;
-                }
-            " } </style>
-
-
-            <div class=classes!("boxes")>
-                <p> { "C4DT" } </p>
-                <p> { "DCL" } </p>
-                <p> { "ineiti" } </p>
-            </div>
+            <Topology/>
         </> }
     }
 }
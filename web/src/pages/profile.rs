@@ -0,0 +1,49 @@
+use at2_ns::Contact;
+use yew::prelude::*;
+
+/// Only small inlined images are shown, other avatars being hashes
+fn avatar_source(contact: &Contact) -> Option<&str> {
+    contact
+        .profile
+        .avatar
+        .as_deref()
+        .filter(|avatar| avatar.starts_with("data:image/"))
+}
+
+/// Name of a contact, with its avatar, linked to its url and its bio on hover,
+/// marked if official
+pub fn view_contact(contact: &Contact) -> Html {
+    let name = match contact.profile.safe_url() {
+        Some(url) => html! {
+            <a href=url.to_owned() target="_blank" rel="noopener noreferrer">
+                { contact.name.clone() }
+            </a>
+        },
+        None => html! { contact.name.clone() },
+    };
+
+    html! {
+        <span title=contact.profile.bio.clone().unwrap_or_default()>
+            { avatar_source(contact).map_or_else(|| html! {}, |src| html! {
+                <img
+                    src=src.to_owned()
+                    alt=""
+                    style=concat!(
+                        "width: 1.25rem;",
+                        "height: 1.25rem;",
+                        "border-radius: 50%;",
+                        "vertical-align: middle;",
+                        "margin-right: 0.25rem;",
+                    )
+                />
+            }) }
+            { name }
+            { if contact.verified { html! {
+                <span
+                    title="Verified by the name service"
+                    style="color: #1e88e5; margin-left: 0.25rem;"
+                >{ "✔" }</span>
+            } } else { html! {} } }
+        </span>
+    }
+}
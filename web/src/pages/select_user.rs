@@ -7,6 +7,7 @@ use material_yew::{
 };
 use yew::{prelude::*, worker::Agent};
 
+use super::profile::view_contact;
 use crate::agents;
 
 #[derive(Properties, Clone)]
@@ -94,9 +95,15 @@ impl Component for SelectUser {
                 })
             >
                 { self.sorted_usernames.iter()
+                    .filter_map(|username| self.username_to_user.get(username))
                     .zip(iter::once(true).chain(iter::repeat(false)))
-                    .map(|(username, selected)| html! {
-                    <MatListItem selected=selected>{ username.clone() }</MatListItem>
+                    .map(|(user, selected)| html! {
+                    <MatListItem selected=selected twoline=user.profile.bio.is_some()>
+                        { view_contact(user) }
+                        { user.profile.bio.as_ref().map_or_else(|| html! {}, |bio| html! {
+                            <span slot="secondary">{ bio }</span>
+                        }) }
+                    </MatListItem>
                 }).collect::<Html>() }
             </MatSelect>
         }
@@ -1,13 +1,16 @@
-use at2_ns::{Contact, User};
-use chrono::{offset::Local, DateTime, Duration};
+use at2_ns::{client::WebClient, Contact, User};
+use at2_speedtest::{Speedtest as SpeedtestState, State, Transfer};
+use chrono::Utc;
 use gloo_timers::callback::{Interval, Timeout};
 use material_yew::{MatButton, MatLinearProgress};
-use yew::{prelude::*, worker::Agent};
+use wasm_bindgen_futures::spawn_local;
+use yew::{prelude::*, services::ConsoleService, worker::Agent};
 
 use super::select_user::SelectUser;
-use crate::agents;
+use crate::{agents, config::Config};
 
 const TRANSFER_PER_REFRESH: usize = 50;
+const MAX_SENDERS: usize = 32;
 
 #[derive(Properties, Clone)]
 pub struct Properties {
@@ -21,47 +24,68 @@ fn validate_amount(amount: &str) -> Option<usize> {
     amount.parse::<usize>().ok()
 }
 
+fn ratio(part: usize, total: usize) -> f32 {
+    if total == 0 {
+        1.0
+    } else {
+        part as f32 / total as f32
+    }
+}
+
+fn validate_senders(senders: &str) -> Option<usize> {
+    senders
+        .parse::<usize>()
+        .ok()
+        .filter(|count| (1..=MAX_SENDERS).contains(count))
+}
+
+/// Agents used by a single sender
+struct SenderAgents {
+    /// Credits it, from the main account
+    fund: Box<dyn Bridge<agents::SendAsset>>,
+    send_asset: Box<dyn Bridge<agents::SendAsset>>,
+    get_last_sequence: Box<dyn Bridge<agents::GetLastSequence>>,
+}
+
 pub struct Speedtest {
     link: ComponentLink<Self>,
     props: Properties,
 
-    send_asset_agent: Box<dyn Bridge<agents::SendAsset>>,
+    get_funding_sequence_agent: Box<dyn Bridge<agents::GetLastSequence>>,
+    name_service: WebClient,
 
     amount: String,
+    senders_count: String,
+    register_senders: bool,
     to_user: Option<Contact>,
 
-    state: State,
+    speedtest: Option<SpeedtestState>,
+    /// Why the senders couldn't be funded, the speedtest being then cancelled
+    funding_error: Option<String>,
+    senders_agents: Vec<SenderAgents>,
+    /// A `Running` message is already queued
+    scheduling: bool,
     #[allow(dead_code)] // never dropped
     last_sequence_refresher: Interval,
 }
 
-pub enum State {
-    Idle,
-    Started {
-        started_at: DateTime<Local>,
-
-        sent_tx: usize,
-        confirmed_tx: usize,
-        total_tx: usize,
-    },
-    Done {
-        elapsed: Duration,
-        total_tx: usize,
-    },
-}
-
 pub enum Message {
-    TransactionSent(<agents::SendAsset as Agent>::Output),
-    GotLastSequence(<agents::GetLastSequence as Agent>::Output),
+    TransactionSent(usize, <agents::SendAsset as Agent>::Output),
+    GotLastSequence(usize, <agents::GetLastSequence as Agent>::Output),
+    FundingSent(usize, <agents::SendAsset as Agent>::Output),
+    GotFundingSequence(<agents::GetLastSequence as Agent>::Output),
+    RefreshSequences,
 
     UpdateTransactionAmount(String),
+    UpdateSendersCount(String),
+    ToggleRegisterSenders,
     SelectUser(Contact),
 
     Start,
-    Running {
-        user_to_send_to: Contact,
-        remaining: usize,
-    },
+    Running,
+    Pause,
+    Resume,
+    Cancel,
 }
 
 impl Component for Speedtest {
@@ -69,128 +93,218 @@ impl Component for Speedtest {
     type Message = Message;
 
     fn create(props: Self::Properties, link: ComponentLink<Self>) -> Self {
-        let send_asset_agent =
-            agents::SendAsset::bridge(link.callback(Self::Message::TransactionSent));
+        let conf = Config::parse();
 
-        let user = props.user.0.clone().to_thin();
-        let mut get_last_sequence_agent =
-            agents::GetLastSequence::bridge(link.callback(Self::Message::GotLastSequence));
+        let get_funding_sequence_agent =
+            agents::GetLastSequence::bridge(link.callback(Self::Message::GotFundingSequence));
+
+        let refresh = link.callback(|_: ()| Self::Message::RefreshSequences);
 
         Self {
             link,
             props,
 
-            send_asset_agent,
+            get_funding_sequence_agent,
+            name_service: WebClient::new(conf.name_service()),
 
             amount: "1000".to_owned(),
+            senders_count: "1".to_owned(),
+            register_senders: false,
             to_user: None,
 
-            state: State::Idle,
-            last_sequence_refresher: Interval::new(100, move || {
-                get_last_sequence_agent.send(user.clone())
-            }),
+            speedtest: None,
+            funding_error: None,
+            senders_agents: Vec::new(),
+            scheduling: false,
+            last_sequence_refresher: Interval::new(100, move || refresh.emit(())),
         }
     }
 
     fn update(&mut self, message: Self::Message) -> ShouldRender {
         match message {
-            Self::Message::TransactionSent(_) => {
-                if let State::Started { sent_tx, .. } = &mut self.state {
-                    *sent_tx += 1;
-
-                    *sent_tx % TRANSFER_PER_REFRESH == 0
+            Self::Message::TransactionSent(index, ret) => {
+                if let Some(speedtest) = &mut self.speedtest {
+                    speedtest.transfer_sent(index, ret.is_ok());
+
+                    speedtest
+                        .senders()
+                        .get(index)
+                        .map(|sender| sender.sent_tx() % TRANSFER_PER_REFRESH == 0)
+                        .unwrap_or_default()
                 } else {
                     false
                 }
             }
 
-            Self::Message::GotLastSequence(ret) => {
-                if let State::Started {
-                    started_at,
-                    confirmed_tx,
-                    total_tx,
-                    ..
-                } = &mut self.state
-                {
-                    if let Ok(seq) = ret {
-                        *confirmed_tx = (seq - self.props.user.1) as usize;
-
-                        if confirmed_tx == total_tx {
-                            self.state = State::Done {
-                                elapsed: Local::now() - *started_at,
-                                total_tx: *total_tx,
-                            };
-
-                            self.props.bump_sequence.emit(seq);
-                        }
+            Self::Message::GotLastSequence(index, ret) => {
+                if let (Some(speedtest), Ok(seq)) = (&mut self.speedtest, ret) {
+                    if let Some(seq) = speedtest.got_sequence(index, seq, Utc::now()) {
+                        self.props.bump_sequence.emit(seq);
                     }
                 }
                 true
             }
 
+            Self::Message::FundingSent(index, ret) => {
+                let failed = ret.is_err();
+                if let Err(err) = ret {
+                    ConsoleService::error(&format!("unable to fund sender: {}", err));
+                    self.funding_error.get_or_insert(err.to_string());
+                }
+                if let Some(speedtest) = &mut self.speedtest {
+                    speedtest.funding_sent(index, !failed);
+                }
+                failed
+            }
+
+            Self::Message::GotFundingSequence(ret) => {
+                if let (Some(speedtest), Ok(seq)) = (&mut self.speedtest, ret) {
+                    if let Some(seq) = speedtest.got_main_sequence(seq, Utc::now()) {
+                        self.props.bump_sequence.emit(seq);
+                        if let State::Started { .. } = speedtest.state() {
+                            self.link.send_message(Self::Message::Running);
+                        }
+
+                        return true;
+                    }
+                }
+                false
+            }
+
+            Self::Message::RefreshSequences => {
+                match self.speedtest.as_ref() {
+                    Some(speedtest) => match speedtest.state() {
+                        State::Funding { .. } | State::CancellingFunding => self
+                            .get_funding_sequence_agent
+                            .send(speedtest.main_user().clone().to_thin()),
+                        State::Started { .. } | State::Paused { .. } | State::Cancelling { .. } => {
+                            speedtest
+                                .senders()
+                                .iter()
+                                .zip(self.senders_agents.iter_mut())
+                                .for_each(|(sender, agents)| {
+                                    agents
+                                        .get_last_sequence
+                                        .send(sender.user().clone().to_thin())
+                                })
+                        }
+                        State::Done { .. } => {}
+                    },
+                    None => {}
+                }
+                false
+            }
+
             Self::Message::UpdateTransactionAmount(amount) => {
                 self.amount = amount;
                 false
             }
+            Self::Message::UpdateSendersCount(count) => {
+                self.senders_count = count;
+                true
+            }
+            Self::Message::ToggleRegisterSenders => {
+                self.register_senders = !self.register_senders;
+                true
+            }
             Self::Message::SelectUser(username) => {
                 self.to_user = Some(username);
                 false
             }
 
             Self::Message::Start => {
-                if let Some(total_tx) = validate_amount(&self.amount) {
-                    if let Some(user_to_send_to) = self.to_user.clone() {
-                        self.state = State::Started {
-                            started_at: Local::now(),
-                            sent_tx: 0,
-                            confirmed_tx: 0,
-                            total_tx,
-                        };
-
-                        self.link.send_message(Self::Message::Running {
-                            user_to_send_to,
-                            remaining: total_tx,
-                        });
+                if let (Some(total_tx), Some(senders_count), Some(recipient)) = (
+                    validate_amount(&self.amount),
+                    validate_senders(&self.senders_count),
+                    self.to_user.clone(),
+                ) {
+                    let (speedtest, funding) = SpeedtestState::start(
+                        self.props.user.clone(),
+                        recipient,
+                        total_tx,
+                        senders_count,
+                        Utc::now(),
+                    );
+
+                    self.funding_error = None;
+                    self.senders_agents = (0..speedtest.senders().len())
+                        .map(|index| self.new_sender_agents(index))
+                        .collect();
+
+                    for transfer in funding {
+                        if let Some(agents) = self.senders_agents.get_mut(transfer.sender) {
+                            Self::send(&mut agents.fund, transfer);
+                        }
                     }
+
+                    if self.register_senders && senders_count > 1 {
+                        for sender in speedtest.senders() {
+                            let (mut client, user) =
+                                (self.name_service.clone(), sender.user().clone());
+
+                            spawn_local(async move {
+                                if let Err(err) = client.put(user).await {
+                                    ConsoleService::error(&format!(
+                                        "unable to register sender: {}",
+                                        err
+                                    ));
+                                }
+                            });
+                        }
+                    }
+
+                    if let State::Started { .. } = speedtest.state() {
+                        self.link.send_message(Self::Message::Running);
+                    }
+                    self.speedtest = Some(speedtest);
                 }
 
                 true
             }
-            Self::Message::Running {
-                user_to_send_to,
-                mut remaining,
-            } => {
-                if let State::Started { total_tx, .. } = self.state {
-                    let sender = self.props.user.0.clone();
-                    let base_sequence =
-                        self.props.user.1 + (total_tx - remaining) as sieve::Sequence + 1;
-
-                    let transfers_count = remaining.min(TRANSFER_PER_REFRESH);
-                    for sequence in
-                        (0..transfers_count).map(|i| base_sequence + i as sieve::Sequence)
-                    {
-                        self.send_asset_agent.send((
-                            sender.clone(),
-                            sequence,
-                            user_to_send_to.clone(), // can't be empty
-                            1,
-                        ));
+            Self::Message::Running => {
+                self.scheduling = false;
+
+                if let Some(speedtest) = &mut self.speedtest {
+                    for transfer in speedtest.next_transfers(TRANSFER_PER_REFRESH) {
+                        if let Some(agents) = self.senders_agents.get_mut(transfer.sender) {
+                            Self::send(&mut agents.send_asset, transfer);
+                        }
                     }
-                    remaining -= transfers_count;
 
                     let callback = self.link.callback(|m| m);
-                    if remaining > 0 {
+                    if matches!(speedtest.state(), State::Started { .. })
+                        && speedtest.has_remaining()
+                    {
                         // trigger refresh
-                        Timeout::new(0, move || {
-                            callback.emit(Self::Message::Running {
-                                user_to_send_to,
-                                remaining,
-                            })
-                        })
-                        .forget();
+                        Timeout::new(0, move || callback.emit(Self::Message::Running)).forget();
+                        self.scheduling = true;
                     }
                 }
 
+                true
+            }
+            Self::Message::Pause => {
+                if let Some(speedtest) = &mut self.speedtest {
+                    speedtest.pause(Utc::now());
+                }
+
+                true
+            }
+            Self::Message::Resume => {
+                if let Some(speedtest) = &mut self.speedtest {
+                    // a pending `Running` will pick up where we left
+                    if speedtest.resume(Utc::now()) && !self.scheduling {
+                        self.link.send_message(Self::Message::Running);
+                    }
+                }
+
+                true
+            }
+            Self::Message::Cancel => {
+                if let Some(speedtest) = &mut self.speedtest {
+                    speedtest.cancel(Utc::now());
+                }
+
                 true
             }
         }
@@ -202,6 +316,13 @@ impl Component for Speedtest {
     }
 
     fn view(&self) -> Html {
+        let state = self.speedtest.as_ref().map(SpeedtestState::state);
+        let running = !matches!(state, None | Some(State::Done { .. }));
+        let cancellable = matches!(
+            state,
+            Some(State::Funding { .. } | State::Started { .. } | State::Paused { .. })
+        );
+
         html! { <>
             <h1> { "Speedtest" } </h1>
 
@@ -232,13 +353,23 @@ impl Component for Speedtest {
                 is below the speed reported in the " }
                 <a href="https://arxiv.org/abs/1812.10844" target="_blank"> { "research paper" } </a>
                 { "." }
-             </p>
+            </p>
+
+            <p> { "
+                As every account has to process its transactions in order,
+                a single sender is limiting the load on the network.
+                You can use " }
+                <b>{ "more senders" }</b>
+                { ", temporary accounts funded by yours, that will send
+                transactions in parallel.
+            " } </p>
 
             <hr />
 
             <span style=concat!(
                 "display: flex;",
                 "align-items: center;",
+                "flex-wrap: wrap;",
             )>
 
                 <label>
@@ -259,29 +390,101 @@ impl Component for Speedtest {
                     />
                 </label>
 
+                <label>
+                    { "Parallel senders " }
+                    <input
+                        oninput=self.link.callback(|event: InputData|
+                            Self::Message::UpdateSendersCount(event.value))
+                        value=self.senders_count.clone()
+                        min=1
+                        max=MAX_SENDERS.to_string()
+                        type={ "number" }
+                    />
+                </label>
+
+                <label hidden=validate_senders(&self.senders_count).unwrap_or(1) == 1>
+                    <input
+                        onclick=self.link.callback(|_| Self::Message::ToggleRegisterSenders)
+                        checked=self.register_senders
+                        type={ "checkbox" }
+                    />
+                    { " Register senders on the name service" }
+                </label>
+
                 <span
                     onclick=self.link.callback(|_| Self::Message::Start)
                 ><MatButton
                     label="Launch"
                     raised=true
-                    disabled=matches!(self.state, State::Started { .. }) || validate_amount(&self.amount).is_none()
+                    disabled=running
+                        || validate_amount(&self.amount).is_none()
+                        || validate_senders(&self.senders_count).is_none()
+                /></span>
+
+                { if let Some(State::Paused { .. }) = state { html! {
+                    <span
+                        onclick=self.link.callback(|_| Self::Message::Resume)
+                    ><MatButton label="Resume" /></span>
+                } } else { html! {
+                    <span
+                        onclick=self.link.callback(|_| Self::Message::Pause)
+                    ><MatButton
+                        label="Pause"
+                        disabled=!matches!(state, Some(State::Started { .. }))
+                    /></span>
+                } } }
+
+                <span
+                    onclick=self.link.callback(|_| Self::Message::Cancel)
+                ><MatButton
+                    label="Cancel"
+                    disabled=!cancellable
                 /></span>
             </span>
 
             <hr />
 
-            { if let State::Started { started_at, sent_tx, confirmed_tx, total_tx } = self.state {
-                  Speedtest::view_speedtest(sent_tx, confirmed_tx, total_tx, Local::now() - started_at)
-             } else if let State::Done { elapsed, total_tx } = self.state {
-                  Speedtest::view_speedtest(total_tx, total_tx, total_tx, elapsed)
-             } else {
-                 html! {}
-             } }
+            { for self.funding_error.iter().map(|err| html! {
+                <p style="text-align: center; color: red"> {
+                    format!("Unable to fund the senders: {}", err)
+                } </p>
+            }) }
+
+            { match &self.speedtest {
+                None => html! {},
+                Some(speedtest) => Self::view_speedtest(speedtest),
+            } }
         </> }
     }
 }
 
 impl Speedtest {
+    fn new_sender_agents(&self, index: usize) -> SenderAgents {
+        SenderAgents {
+            fund: agents::SendAsset::bridge(
+                self.link
+                    .callback(move |ret| Message::FundingSent(index, ret)),
+            ),
+            send_asset: agents::SendAsset::bridge(
+                self.link
+                    .callback(move |ret| Message::TransactionSent(index, ret)),
+            ),
+            get_last_sequence: agents::GetLastSequence::bridge(
+                self.link
+                    .callback(move |ret| Message::GotLastSequence(index, ret)),
+            ),
+        }
+    }
+
+    fn send(agent: &mut Box<dyn Bridge<agents::SendAsset>>, transfer: Transfer) {
+        agent.send((
+            transfer.user,
+            transfer.sequence,
+            transfer.recipient,
+            transfer.amount,
+        ));
+    }
+
     fn progress_bar(progress: f32) -> Html {
         assert!(
             (0.0..=1.0).contains(&progress),
@@ -302,38 +505,72 @@ impl Speedtest {
         }
     }
 
-    fn view_speedtest(
-        sent_tx: usize,
-        confirmed_tx: usize,
-        total_tx: usize,
-        elapsed: Duration,
-    ) -> Html {
-        let tps = (confirmed_tx as u64 * 1000).checked_div(elapsed.num_milliseconds() as u64);
+    fn view_speedtest(speedtest: &SpeedtestState) -> Html {
+        let metrics = speedtest.metrics(Utc::now());
+        let elapsed_ms = metrics.elapsed_ms;
 
         const FIRST_COL: &str = "text-align: end; padding: 0 1em";
 
         html! { <>
+            { match speedtest.state() {
+                State::Funding { .. } => html! {
+                    <p style="text-align: center"> {
+                        format!("Funding {} senders…", metrics.senders.len())
+                    } </p>
+                },
+                State::Paused { .. } => html! {
+                    <p style="text-align: center"> { "Paused" } </p>
+                },
+                State::CancellingFunding => html! {
+                    <p style="text-align: center"> {
+                        "Cancelling, waiting for the senders' funding to be processed…"
+                    } </p>
+                },
+                State::Cancelling { .. } => html! {
+                    <p style="text-align: center"> {
+                        "Cancelling, waiting for the sent transactions to be processed…"
+                    } </p>
+                },
+                State::Done { cancelled: true, .. } => html! {
+                    <p style="text-align: center"> { "Cancelled" } </p>
+                },
+                State::Started { .. } | State::Done { cancelled: false, .. } => html! {},
+            } }
+
             <table>
                 <tr>
                     <td style=FIRST_COL> { "Transactions sent" } </td>
-                    <td> { Speedtest::progress_bar(sent_tx as f32/total_tx as f32) } </td>
+                    <td> { Speedtest::progress_bar(ratio(metrics.sent_tx, metrics.total_tx)) } </td>
                 </tr>
                 <tr>
                     <td style=FIRST_COL> { "Transactions confirmed" } </td>
-                    <td> { Speedtest::progress_bar(confirmed_tx as f32/total_tx as f32) } </td>
+                    <td> { Speedtest::progress_bar(ratio(metrics.confirmed_tx, metrics.total_tx)) } </td>
                 </tr>
 
                 <tr>
                     <td style=FIRST_COL> { "Running for" } </td>
-                    <td> { format!("{:.1}s", elapsed.num_milliseconds() as f64 / 1000.0) } </td>
+                    <td> { format!("{:.1}s", elapsed_ms as f64 / 1000.0) } </td>
                 </tr>
 
                 <tr>
                     <td style=FIRST_COL> { "AT2's computed TPS" } </td>
-                    <td> { tps.unwrap_or(0) } </td>
+                    <td> { metrics.tps } </td>
                 </tr>
             </table>
 
+            { if metrics.senders.len() > 1 { html! {
+                <table>
+                    { for metrics.senders.iter().map(|sender| html! {
+                        <tr>
+                            <td style=FIRST_COL> { &sender.name } </td>
+                            <td> { Speedtest::progress_bar(
+                                ratio(sender.confirmed_tx, sender.total_tx)
+                            ) } </td>
+                        </tr>
+                    }) }
+                </table>
+            } } else { html! {} } }
+
             <p style="text-align: center">
             { " For reference, the next table shows the TPS of
                 other distributed ledgers." }
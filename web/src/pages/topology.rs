@@ -0,0 +1,155 @@
+use gloo_timers::callback::{Interval, Timeout};
+use js_sys::{Array, Date, Function, Object, Promise, Reflect};
+use wasm_bindgen::JsValue;
+use wasm_bindgen_futures::{spawn_local, JsFuture};
+use yew::prelude::*;
+
+use crate::config::{Config, Node};
+
+/// Milliseconds between two probes of the same node
+const PROBE_INTERVAL: u32 = 5_000;
+/// Milliseconds after which a node not answering is unreachable
+const PROBE_TIMEOUT: u32 = 3_000;
+
+enum Status {
+    Unknown,
+    Reachable { latency: f64 },
+    Unreachable,
+}
+
+pub enum Message {
+    Probe,
+    Probed(usize, Option<f64>),
+}
+
+/// Live status of the nodes of the network, as seen from the browser
+pub struct Topology {
+    link: ComponentLink<Self>,
+
+    nodes: Vec<(Node, Status)>,
+    #[allow(dead_code)] // never dropped
+    prober: Interval,
+}
+
+/// Promise rejected after the given milliseconds
+fn timeout(millis: u32) -> Promise {
+    Promise::new(&mut |_, reject| {
+        Timeout::new(millis, move || {
+            let _ = reject.call1(&JsValue::NULL, &"timed out".into());
+        })
+        .forget();
+    })
+}
+
+/// Time a request to the node, without reading the reply, as it has to only be reachable
+async fn probe(node: Node) -> Result<f64, JsValue> {
+    let options = Object::new();
+    Reflect::set(&options, &"mode".into(), &"no-cors".into())?;
+    Reflect::set(&options, &"cache".into(), &"no-store".into())?;
+
+    let global = js_sys::global();
+    let fetch = Function::from(Reflect::get(&global, &"fetch".into())?);
+
+    let start = Date::now();
+    let request = fetch.call2(&global, &node.uri.to_string().into(), &options)?;
+    // otherwise, a node silently dropping the request stays probing
+    JsFuture::from(Promise::race(&Array::of2(
+        &request,
+        &timeout(PROBE_TIMEOUT),
+    )))
+    .await?;
+
+    Ok(Date::now() - start)
+}
+
+impl Component for Topology {
+    type Properties = ();
+    type Message = Message;
+
+    fn create(_: Self::Properties, link: ComponentLink<Self>) -> Self {
+        let nodes = Config::parse()
+            .nodes()
+            .iter()
+            .cloned()
+            .map(|node| (node, Status::Unknown))
+            .collect();
+
+        let probe = link.callback(|_: ()| Message::Probe);
+        link.send_message(Message::Probe);
+
+        Self {
+            link,
+
+            nodes,
+            prober: Interval::new(PROBE_INTERVAL, move || {
+                probe.emit(());
+            }),
+        }
+    }
+
+    fn update(&mut self, message: Message) -> ShouldRender {
+        match message {
+            Message::Probe => {
+                for (index, (node, _)) in self.nodes.iter().enumerate() {
+                    let (node, probed) = (node.clone(), self.link.callback(|ret| ret));
+
+                    spawn_local(async move {
+                        probed.emit(Message::Probed(index, probe(node).await.ok()));
+                    });
+                }
+
+                false
+            }
+            Message::Probed(index, latency) => {
+                if let Some((_, status)) = self.nodes.get_mut(index) {
+                    *status = match latency {
+                        Some(latency) => Status::Reachable { latency },
+                        None => Status::Unreachable,
+                    };
+                }
+
+                true
+            }
+        }
+    }
+
+    fn change(&mut self, _: Self::Properties) -> ShouldRender {
+        false
+    }
+
+    fn view(&self) -> Html {
+        html! { <>
+            <style> { "
+                .nodes {
+                    display: flex;
+                    flex-wrap: wrap;
+                    justify-content: space-around;
+                }
+
+                .nodes > * {
+                    border: solid;
+                    padding: 1em 2em;
+                    margin: 0.5em;
+                }
+            " } </style>
+
+            <div class=classes!("nodes")>
+                { for self.nodes.iter().map(|(node, status)| {
+                    let (color, text) = match status {
+                        Status::Unknown => ("grey", "probing".to_owned()),
+                        Status::Reachable { latency } => ("green", format!("{:.0} ms", latency)),
+                        Status::Unreachable => ("violet", "unreachable".to_owned()),
+                    };
+
+                    html! {
+                        <div title=node.uri.to_string()>
+                            <b>{ node.name.clone() }</b>
+                            <br />
+                            <span style=format!("color: {};", color)>{ "● " } { text }</span>
+                        </div>
+                    }
+                }) }
+            </div>
+        </> }
+    }
+}
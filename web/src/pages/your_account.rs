@@ -1,15 +1,18 @@
-use std::collections::HashMap;
+use std::{
+    collections::{HashMap, HashSet},
+    time::SystemTime,
+};
 
 use at2_node::{FullTransaction, TransactionState};
-use at2_ns::{Contact, User};
-use chrono::Utc;
+use at2_ns::{Contact, History, User};
+use chrono::{DateTime, Utc};
 use chrono_humanize::HumanTime;
 use drop::crypto::sign;
 use gloo_timers::callback::Interval;
 use material_yew::{MatButton, MatFormfield};
 use yew::{prelude::*, worker::Agent};
 
-use super::select_user::SelectUser;
+use super::{balance_history::BalanceHistory, identity::Identity, select_user::SelectUser};
 use crate::agents;
 
 #[derive(Properties, Clone)]
@@ -27,6 +30,7 @@ pub struct YourAccount {
 
     get_balance_agent: Box<dyn Bridge<agents::GetBalance>>,
     user_balance: Option<u64>,
+    balance_history: BalanceHistory,
 
     send_asset_agent: Box<dyn Bridge<agents::SendAsset>>,
     user_to_send_to: Option<Contact>,
@@ -37,7 +41,11 @@ pub struct YourAccount {
     latest_transactions: Vec<FullTransaction>,
     #[allow(dead_code)] // never dropped
     get_users_agent: Box<dyn Bridge<agents::GetUsers>>,
-    pubkey_to_username: HashMap<sign::PublicKey, String>,
+    pubkey_to_contact: HashMap<sign::PublicKey, Contact>,
+    get_history_agent: Box<dyn Bridge<agents::GetHistory>>,
+    /// Only of the parties of the latest transactions, empty if never registered
+    pubkey_to_history: HashMap<sign::PublicKey, History>,
+    fetching_history: HashSet<sign::PublicKey>,
     #[allow(dead_code)] // never dropped
     refresher: Interval,
 }
@@ -52,6 +60,7 @@ pub enum Message {
 
     LatestTransactionsGot(<agents::GetLatestTransactions as Agent>::Output),
     GotUsers(<agents::GetUsers as Agent>::Output),
+    GotHistory(<agents::GetHistory as Agent>::Output),
     Refresh,
 }
 
@@ -67,6 +76,57 @@ fn validate_amount(max: &Option<u64>, amount: &str) -> Option<u64> {
     Some(amount)
 }
 
+impl YourAccount {
+    /// Show the name used when the transaction happened, as it might have changed since
+    /// Fetch the histories of the shown parties, unless known and not renamed since
+    fn fetch_histories(&mut self) {
+        let parties = self
+            .latest_transactions
+            .iter()
+            .flat_map(|tx| [tx.sender, tx.recipient])
+            .collect::<HashSet<_>>();
+
+        for public_key in parties {
+            let outdated = match self.pubkey_to_history.get(&public_key) {
+                None => true,
+                Some(history) => self
+                    .pubkey_to_contact
+                    .get(&public_key)
+                    .map_or(false, |contact| {
+                        history.current() != Some(contact.name.as_str())
+                    }),
+            };
+
+            if outdated && self.fetching_history.insert(public_key) {
+                self.get_history_agent.send(public_key);
+            }
+        }
+    }
+
+    fn view_party(&self, public_key: &sign::PublicKey, at: DateTime<Utc>) -> Html {
+        let name_at = self
+            .pubkey_to_history
+            .get(public_key)
+            .and_then(|history| history.name_at(SystemTime::from(at)));
+
+        match (name_at, self.pubkey_to_contact.get(public_key)) {
+            (Some(name), Some(contact)) if name != contact.name => html! { <>
+                { name }
+                <span style="color: grey">
+                    { " (now " }
+                    <Identity public_key=*public_key contact=Some(contact.clone()) />
+                    { ")" }
+                </span>
+            </> },
+            (_, Some(contact)) => html! {
+                <Identity public_key=*public_key contact=Some(contact.clone()) />
+            },
+            (Some(name), None) => html! { name },
+            (None, None) => html! { <Identity public_key=*public_key /> },
+        }
+    }
+}
+
 impl Component for YourAccount {
     type Properties = Properties;
     type Message = Message;
@@ -76,6 +136,7 @@ impl Component for YourAccount {
         let get_latest_transactions_agent =
             agents::GetLatestTransactions::bridge(link.callback(Message::LatestTransactionsGot));
         let get_users_agent = agents::GetUsers::bridge(link.callback(Message::GotUsers));
+        let get_history_agent = agents::GetHistory::bridge(link.callback(Message::GotHistory));
 
         let mut get_balance_agent = agents::GetBalance::bridge(link.callback(Message::GotBalance));
         get_balance_agent.send(props.user.0.clone().to_thin());
@@ -88,6 +149,7 @@ impl Component for YourAccount {
 
             get_balance_agent,
             user_balance: None,
+            balance_history: BalanceHistory::default(),
 
             send_asset_agent,
             user_to_send_to: None,
@@ -96,7 +158,10 @@ impl Component for YourAccount {
             get_latest_transactions_agent,
             latest_transactions: Vec::new(),
             get_users_agent,
-            pubkey_to_username: HashMap::new(),
+            pubkey_to_contact: HashMap::new(),
+            get_history_agent,
+            pubkey_to_history: HashMap::new(),
+            fetching_history: HashSet::new(),
             refresher: Interval::new(1000, move || {
                 refresh.emit(());
             }),
@@ -107,7 +172,10 @@ impl Component for YourAccount {
         match message {
             Message::GotBalance(res) => {
                 match res {
-                    Ok(balance) => self.user_balance = Some(balance),
+                    Ok(balance) => {
+                        self.user_balance = Some(balance);
+                        self.balance_history.record_balance(Utc::now(), balance);
+                    }
                     Err(_) => self
                         .get_balance_agent
                         .send(self.props.user.0.clone().to_thin()),
@@ -148,7 +216,10 @@ impl Component for YourAccount {
 
             Message::LatestTransactionsGot(mut latest_transactions) => {
                 latest_transactions.reverse();
+                self.balance_history
+                    .record_transactions(&self.props.user.0.public_key(), &latest_transactions);
                 self.latest_transactions = latest_transactions;
+                self.fetch_histories();
 
                 self.get_balance_agent
                     .send(self.props.user.0.clone().to_thin());
@@ -156,14 +227,26 @@ impl Component for YourAccount {
                 true
             }
             Message::GotUsers(users) => {
-                self.pubkey_to_username = users
+                self.pubkey_to_contact = users
                     .into_iter()
-                    .map(|user| (*user.public_key(), user.name.clone()))
+                    .map(|user| (*user.public_key(), user))
                     .collect();
+                self.fetch_histories();
 
                 true
             }
 
+            Message::GotHistory((public_key, Ok(history))) => {
+                self.fetching_history.remove(&public_key);
+                self.pubkey_to_history.insert(public_key, history);
+                true
+            }
+            // fetched again on the next refresh
+            Message::GotHistory((public_key, Err(_))) => {
+                self.fetching_history.remove(&public_key);
+                false
+            }
+
             Message::Refresh => true,
         }
     }
@@ -213,6 +296,7 @@ impl Component for YourAccount {
                     .unwrap_or(html! { <span style="color: lightgrey"> { "fetching" } </span> }) }
             </p>
 
+            { self.balance_history.view(now) }
 
             <span style=concat!(
                 "display: flex;",
@@ -284,9 +368,9 @@ impl Component for YourAccount {
                               TransactionState::Failure => html! { <span style="color: violet">{ "failure" }</span> },
                           }}</td>
                           <td style="padding: 0 2px;">
-                            { self.pubkey_to_username.get(&tx.sender).unwrap_or(&tx.sender.to_string()) }
+                            { self.view_party(&tx.sender, tx.timestamp) }
                             { " -> " }
-                            { self.pubkey_to_username.get(&tx.recipient).unwrap_or(&tx.recipient.to_string()) }</td>
+                            { self.view_party(&tx.recipient, tx.timestamp) }</td>
                           <td style="padding: 0 2px;">{ tx.amount } { " ₳" }</td>
                       </tr>
                     }) }